@@ -16,7 +16,7 @@ pub enum Alignment {
 impl From<Horizontal> for Alignment {
     fn from(horizontal: Horizontal) -> Self {
         match horizontal {
-            Horizontal::Left => Self::Start,
+            Horizontal::Left | Horizontal::Justify => Self::Start,
             Horizontal::Center => Self::Center,
             Horizontal::Right => Self::End,
         }
@@ -26,7 +26,7 @@ impl From<Horizontal> for Alignment {
 impl From<Vertical> for Alignment {
     fn from(vertical: Vertical) -> Self {
         match vertical {
-            Vertical::Top => Self::Start,
+            Vertical::Top | Vertical::Baseline => Self::Start,
             Vertical::Center => Self::Center,
             Vertical::Bottom => Self::End,
         }
@@ -44,6 +44,10 @@ pub enum Horizontal {
 
     /// Align right
     Right,
+
+    /// Align left, stretching the spacing between words so every line but
+    /// the last one of a paragraph fills the available width
+    Justify,
 }
 
 /// The vertical [`Alignment`] of some resource.
@@ -57,4 +61,7 @@ pub enum Vertical {
 
     /// Align bottom
     Bottom,
+
+    /// Align the first baseline of some text
+    Baseline,
 }
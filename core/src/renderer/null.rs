@@ -1,3 +1,4 @@
+use crate::font;
 use crate::renderer::{self, Renderer};
 use crate::text::{self, Text};
 use crate::{Background, Font, Point, Rectangle, Size, Vector};
@@ -54,7 +55,12 @@ impl text::Renderer for Null {
         16.0
     }
 
-    fn load_font(&mut self, _font: Cow<'static, [u8]>) {}
+    fn load_font(
+        &mut self,
+        _font: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error> {
+        Ok(())
+    }
 
     fn measure(
         &self,
@@ -1,5 +1,6 @@
 //! Draw and interact with text.
 use crate::alignment;
+use crate::font;
 use crate::{Color, Pixels, Point, Rectangle, Size};
 
 use std::borrow::Cow;
@@ -205,7 +206,10 @@ pub trait Renderer: crate::Renderer {
     ) -> Option<Hit>;
 
     /// Loads a [`Self::Font`] from its bytes.
-    fn load_font(&mut self, font: Cow<'static, [u8]>);
+    fn load_font(
+        &mut self,
+        font: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error>;
 
     /// Draws the given [`Text`].
     fn fill_text(&mut self, text: Text<'_, Self::Font>);
@@ -206,14 +206,22 @@ pub fn draw<Renderer>(
 {
     let bounds = layout.bounds();
 
+    // The layout of a text widget has no baseline, so it is aligned to its top
+    let vertical_alignment = match vertical_alignment {
+        alignment::Vertical::Baseline => alignment::Vertical::Top,
+        vertical_alignment => vertical_alignment,
+    };
+
     let x = match horizontal_alignment {
-        alignment::Horizontal::Left => bounds.x,
+        alignment::Horizontal::Left | alignment::Horizontal::Justify => {
+            bounds.x
+        }
         alignment::Horizontal::Center => bounds.center_x(),
         alignment::Horizontal::Right => bounds.x + bounds.width,
     };
 
     let y = match vertical_alignment {
-        alignment::Vertical::Top => bounds.y,
+        alignment::Vertical::Top | alignment::Vertical::Baseline => bounds.y,
         alignment::Vertical::Center => bounds.center_y(),
         alignment::Vertical::Bottom => bounds.y + bounds.height,
     };
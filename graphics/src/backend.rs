@@ -1,4 +1,5 @@
 //! Write a graphics backend.
+use iced_core::font;
 use iced_core::image;
 use iced_core::svg;
 use iced_core::text;
@@ -75,7 +76,10 @@ pub trait Text {
     ) -> Option<text::Hit>;
 
     /// Loads a [`Font`] from its bytes.
-    fn load_font(&mut self, font: Cow<'static, [u8]>);
+    fn load_font(
+        &mut self,
+        font: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error>;
 }
 
 /// A graphics backend that supports image rendering.
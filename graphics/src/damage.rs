@@ -23,7 +23,8 @@ impl<T: Damage> Damage for Primitive<T> {
                 let mut bounds = *bounds;
 
                 bounds.x = match horizontal_alignment {
-                    alignment::Horizontal::Left => bounds.x,
+                    alignment::Horizontal::Left
+                    | alignment::Horizontal::Justify => bounds.x,
                     alignment::Horizontal::Center => {
                         bounds.x - bounds.width / 2.0
                     }
@@ -36,8 +37,15 @@ impl<T: Damage> Damage for Primitive<T> {
                         bounds.y - bounds.height / 2.0
                     }
                     alignment::Vertical::Bottom => bounds.y - bounds.height,
+                    alignment::Vertical::Baseline => bounds.y - bounds.height,
                 };
 
+                // The baseline is only known once laid out, so the text may
+                // be on either side of it
+                if *vertical_alignment == alignment::Vertical::Baseline {
+                    bounds.height *= 2.0;
+                }
+
                 bounds.expand(1.5)
             }
             Self::Quad { bounds, .. }
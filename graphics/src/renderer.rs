@@ -2,6 +2,7 @@
 use crate::backend::{self, Backend};
 use crate::Primitive;
 
+use iced_core::font;
 use iced_core::image;
 use iced_core::layout;
 use iced_core::renderer;
@@ -197,8 +198,11 @@ where
         )
     }
 
-    fn load_font(&mut self, bytes: Cow<'static, [u8]>) {
-        self.backend.load_font(bytes);
+    fn load_font(
+        &mut self,
+        bytes: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error> {
+        self.backend.load_font(bytes)
     }
 
     fn fill_text(&mut self, text: Text<'_, Self::Font>) {
@@ -1,3 +1,4 @@
+use crate::core::font;
 use crate::core::text;
 use crate::core::{Font, Point, Size};
 use crate::graphics::backend;
@@ -77,8 +78,11 @@ impl backend::Text for Backend {
         )
     }
 
-    fn load_font(&mut self, font: Cow<'static, [u8]>) {
-        delegate!(self, backend, backend.load_font(font));
+    fn load_font(
+        &mut self,
+        font: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error> {
+        delegate!(self, backend, backend.load_font(font))
     }
 }
 
@@ -14,6 +14,7 @@ pub use settings::Settings;
 #[cfg(feature = "geometry")]
 pub use geometry::Geometry;
 
+use crate::core::font;
 use crate::core::renderer;
 use crate::core::text::{self, Text};
 use crate::core::{Background, Font, Point, Rectangle, Size, Vector};
@@ -199,8 +200,11 @@ impl<T> text::Renderer for Renderer<T> {
         )
     }
 
-    fn load_font(&mut self, bytes: Cow<'static, [u8]>) {
-        delegate!(self, renderer, renderer.load_font(bytes));
+    fn load_font(
+        &mut self,
+        bytes: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error> {
+        delegate!(self, renderer, renderer.load_font(bytes))
     }
 
     fn fill_text(&mut self, text: Text<'_, Self::Font>) {
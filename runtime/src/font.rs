@@ -4,10 +4,6 @@ pub use iced_core::font::*;
 use crate::command::{self, Command};
 use std::borrow::Cow;
 
-/// An error while loading a font.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Error {}
-
 /// Load a font from its bytes.
 pub fn load(
     bytes: impl Into<Cow<'static, [u8]>>,
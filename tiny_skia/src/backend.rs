@@ -1,3 +1,4 @@
+use crate::core::font;
 use crate::core::text;
 use crate::core::Gradient;
 use crate::core::{Background, Color, Font, Point, Rectangle, Size, Vector};
@@ -833,8 +834,11 @@ impl backend::Text for Backend {
         )
     }
 
-    fn load_font(&mut self, font: Cow<'static, [u8]>) {
-        self.text_pipeline.load_font(font);
+    fn load_font(
+        &mut self,
+        font: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error> {
+        self.text_pipeline.load_font(font)
     }
 }
 
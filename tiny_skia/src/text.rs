@@ -31,12 +31,27 @@ impl Pipeline {
         }
     }
 
-    pub fn load_font(&mut self, bytes: Cow<'static, [u8]>) {
-        self.font_system.get_mut().db_mut().load_font_source(
+    pub fn load_font(
+        &mut self,
+        bytes: Cow<'static, [u8]>,
+    ) -> Result<(), font::Error> {
+        let has_signature = has_font_signature(&bytes);
+
+        let faces = self.font_system.get_mut().db_mut().load_font_source(
             cosmic_text::fontdb::Source::Binary(Arc::new(bytes.into_owned())),
         );
 
+        if faces.is_empty() {
+            return Err(if has_signature {
+                font::Error::Parse
+            } else {
+                font::Error::NoFaces
+            });
+        }
+
         self.cache = RefCell::new(Cache::new());
+
+        Ok(())
     }
 
     pub fn draw(
@@ -64,6 +79,7 @@ impl Pipeline {
             size,
             line_height,
             shaping,
+            justify: horizontal_alignment == alignment::Horizontal::Justify,
         };
 
         let (_, entry) = self.cache.get_mut().allocate(font_system, key);
@@ -74,7 +90,9 @@ impl Pipeline {
         let bounds = bounds * scale_factor;
 
         let x = match horizontal_alignment {
-            alignment::Horizontal::Left => bounds.x,
+            alignment::Horizontal::Left | alignment::Horizontal::Justify => {
+                bounds.x
+            }
             alignment::Horizontal::Center => bounds.x - max_width / 2.0,
             alignment::Horizontal::Right => bounds.x - max_width,
         };
@@ -83,6 +101,15 @@ impl Pipeline {
             alignment::Vertical::Top => bounds.y,
             alignment::Vertical::Center => bounds.y - total_height / 2.0,
             alignment::Vertical::Bottom => bounds.y - total_height,
+            alignment::Vertical::Baseline => {
+                let baseline = entry
+                    .buffer
+                    .layout_runs()
+                    .next()
+                    .map_or(0.0, |run| run.line_y);
+
+                bounds.y - baseline * scale_factor
+            }
         };
 
         let mut swash = cosmic_text::SwashCache::new();
@@ -145,6 +172,7 @@ impl Pipeline {
                 font,
                 bounds,
                 shaping,
+                justify: false,
             },
         );
 
@@ -175,6 +203,7 @@ impl Pipeline {
                 font,
                 bounds,
                 shaping,
+                justify: false,
             },
         );
 
@@ -194,6 +223,15 @@ fn measure(buffer: &cosmic_text::Buffer) -> Size {
     Size::new(width, total_lines as f32 * buffer.metrics().line_height)
 }
 
+fn has_font_signature(bytes: &[u8]) -> bool {
+    const SIGNATURES: [&[u8]; 5] =
+        [b"\x00\x01\x00\x00", b"OTTO", b"true", b"typ1", b"ttcf"];
+
+    SIGNATURES
+        .iter()
+        .any(|signature| bytes.starts_with(signature))
+}
+
 fn to_family(family: font::Family) -> cosmic_text::Family<'static> {
     match family {
         font::Family::Name(name) => cosmic_text::Family::Name(name),
@@ -233,6 +271,14 @@ fn to_stretch(stretch: font::Stretch) -> cosmic_text::Stretch {
     }
 }
 
+fn to_style(style: font::Style) -> cosmic_text::Style {
+    match style {
+        font::Style::Normal => cosmic_text::Style::Normal,
+        font::Style::Italic => cosmic_text::Style::Italic,
+        font::Style::Oblique => cosmic_text::Style::Oblique,
+    }
+}
+
 fn to_shaping(shaping: Shaping) -> cosmic_text::Shaping {
     match shaping {
         Shaping::Basic => cosmic_text::Shaping::Basic,
@@ -397,13 +443,13 @@ impl Cache {
         }
 
         if let hash_map::Entry::Vacant(entry) = self.entries.entry(hash) {
-            let metrics = cosmic_text::Metrics::new(key.size, key.size * 1.2);
+            let metrics = cosmic_text::Metrics::new(key.size, key.line_height);
             let mut buffer = cosmic_text::Buffer::new(font_system, metrics);
 
             buffer.set_size(
                 font_system,
                 key.bounds.width,
-                key.bounds.height.max(key.size * 1.2),
+                key.bounds.height.max(key.line_height),
             );
             buffer.set_text(
                 font_system,
@@ -411,10 +457,19 @@ impl Cache {
                 cosmic_text::Attrs::new()
                     .family(to_family(key.font.family))
                     .weight(to_weight(key.font.weight))
-                    .stretch(to_stretch(key.font.stretch)),
+                    .stretch(to_stretch(key.font.stretch))
+                    .style(to_style(key.font.style)),
                 to_shaping(key.shaping),
             );
 
+            if key.justify {
+                for line in &mut buffer.lines {
+                    let _ = line.set_align(Some(cosmic_text::Align::Justified));
+                }
+
+                buffer.shape_until_scroll(font_system);
+            }
+
             let bounds = measure(&buffer);
 
             let _ = entry.insert(Entry { buffer, bounds });
@@ -464,6 +519,7 @@ struct Key<'a> {
     font: Font,
     bounds: Size,
     shaping: Shaping,
+    justify: bool,
 }
 
 impl Key<'_> {
@@ -475,6 +531,7 @@ impl Key<'_> {
         self.bounds.width.to_bits().hash(&mut hasher);
         self.bounds.height.to_bits().hash(&mut hasher);
         self.shaping.hash(&mut hasher);
+        self.justify.hash(&mut hasher);
 
         hasher.finish()
     }
@@ -1,8 +1,9 @@
 use crate::core;
-use crate::core::{Color, Font, Point, Size};
+use crate::core::{Color, Font, Point, Rectangle, Size};
 use crate::graphics::backend;
 use crate::graphics::color;
 use crate::graphics::{Transformation, Viewport};
+use crate::layer;
 use crate::primitive::{self, Primitive};
 use crate::quad;
 use crate::text;
@@ -32,6 +33,7 @@ pub struct Backend {
 
     default_font: Font,
     default_text_size: f32,
+    text_preparation: text::Preparation,
 }
 
 impl Backend {
@@ -42,7 +44,8 @@ impl Backend {
         settings: Settings,
         format: wgpu::TextureFormat,
     ) -> Self {
-        let text_pipeline = text::Pipeline::new(device, queue, format);
+        let text_pipeline =
+            text::Pipeline::with_settings(device, queue, format, &settings);
         let quad_pipeline = quad::Pipeline::new(device, format);
         let triangle_pipeline =
             triangle::Pipeline::new(device, format, settings.antialiasing);
@@ -60,6 +63,7 @@ impl Backend {
 
             default_font: settings.default_font,
             default_text_size: settings.default_text_size,
+            text_preparation: text::Preparation::Prepared,
         }
     }
 
@@ -88,6 +92,8 @@ impl Backend {
 
         let mut layers = Layer::generate(primitives, viewport);
 
+        self.text_preparation = text::Preparation::Prepared;
+
         if !overlay_text.is_empty() {
             layers.push(Layer::overlay(overlay_text, viewport));
         }
@@ -120,6 +126,544 @@ impl Backend {
         self.image_pipeline.end_frame();
     }
 
+    /// Measures the given [`primitive::Text`] and returns the minimum bounds
+    /// that can fit its contents.
+    pub fn measure_text(&self, text: &primitive::Text) -> Size {
+        self.text_pipeline.measure_text(&layer::Text::from(text))
+    }
+
+    /// Measures all of the given [`text::MeasureRequest`]s at once.
+    ///
+    /// The results are the same as measuring each request separately, but
+    /// the text cache is only accessed once for the whole batch.
+    pub fn measure_batch(
+        &self,
+        requests: &[text::MeasureRequest<'_>],
+    ) -> Vec<Size> {
+        self.text_pipeline.measure_batch(requests)
+    }
+
+    /// Returns the [`text::Complexity`] of the given [`text::MeasureRequest`]
+    /// once laid out.
+    ///
+    /// This can be used to estimate how expensive some text is to draw.
+    pub fn text_complexity(
+        &self,
+        request: &text::MeasureRequest<'_>,
+    ) -> text::Complexity {
+        self.text_pipeline.complexity(request)
+    }
+
+    /// Returns the characters of the given content that no loaded font can
+    /// display, even after font fallback.
+    ///
+    /// Each character is only reported once, in order of appearance.
+    pub fn missing_glyphs(&self, content: &str, font: Font) -> Vec<char> {
+        self.text_pipeline.missing_glyphs(content, font)
+    }
+
+    /// Returns the size of a single cell of a grid of text with the given
+    /// size and monospaced [`Font`].
+    ///
+    /// The width of a cell is the advance of the font and its height uses the
+    /// default [`LineHeight`]. The result is cached until the fonts change.
+    ///
+    /// A warning is logged if the [`Font`] is not actually monospaced.
+    ///
+    /// [`LineHeight`]: crate::core::text::LineHeight
+    pub fn cell_size(&self, size: f32, font: Font) -> Size {
+        self.text_pipeline.cell_size(size, font)
+    }
+
+    /// Measures the given content with a monospaced [`Font`], wrapping it at
+    /// the given amount of columns instead of a width.
+    ///
+    /// The width of a column is given by [`Backend::cell_size`], and lines
+    /// are wrapped between any two characters, like in a terminal.
+    pub fn measure_columns(
+        &self,
+        content: &str,
+        size: f32,
+        font: Font,
+        columns: usize,
+        shaping: core::text::Shaping,
+    ) -> Size {
+        self.text_pipeline
+            .measure_columns(content, size, font, columns, shaping)
+    }
+
+    /// Returns the width of every visual line of the given
+    /// [`text::MeasureRequest`], once laid out.
+    ///
+    /// Wrapped lines count as multiple visual lines.
+    pub fn line_widths(&self, request: &text::MeasureRequest<'_>) -> Vec<f32> {
+        self.text_pipeline.line_widths(request)
+    }
+
+    /// Returns the distance from the top of the given
+    /// [`text::MeasureRequest`] to its first baseline, once laid out.
+    ///
+    /// The distance takes the line height of the request into account.
+    pub fn first_baseline(&self, request: &text::MeasureRequest<'_>) -> f32 {
+        self.text_pipeline.first_baseline(request)
+    }
+
+    /// Returns the byte offsets of the contents of the given
+    /// [`text::MeasureRequest`] where lines are wrapped to fit its bounds.
+    ///
+    /// Explicit line breaks in the contents are not included.
+    pub fn line_breaks(
+        &self,
+        request: &text::MeasureRequest<'_>,
+    ) -> Vec<usize> {
+        self.text_pipeline.line_breaks(request)
+    }
+
+    /// Measures the given [`primitive::Text`] without its height limit and
+    /// returns its [`text::Overflow`].
+    ///
+    /// This can be used to find out whether the text fits its bounds.
+    pub fn measure_overflow(&self, text: &primitive::Text) -> text::Overflow {
+        self.text_pipeline
+            .measure_overflow(&layer::Text::from(text))
+    }
+
+    /// Returns whether the contents of the given [`text::MeasureRequest`] fit
+    /// entirely within both the width and the height of its bounds.
+    ///
+    /// Lines are only laid out until the first one past the available
+    /// height, which is cheaper than a full measurement of long contents
+    /// when only a yes or no is needed.
+    pub fn text_fits(&self, request: &text::MeasureRequest<'_>) -> bool {
+        self.text_pipeline.fits(request)
+    }
+
+    /// Returns the largest text size between `min_size` and the size of the
+    /// given [`text::MeasureRequest`] that lets its content fit within its
+    /// bounds.
+    ///
+    /// The line height and shaping of the request are used for every
+    /// measurement, so the result matches the text once drawn with them.
+    ///
+    /// The size is found with a binary search, and every measurement is
+    /// cached like any other. A [`primitive::Wrap::None`] keeps the content
+    /// in a single line, so only its width can shrink it.
+    ///
+    /// If the content does not fit even at `min_size`, then `min_size` is
+    /// returned anyway, and the content overflows its bounds once drawn. A
+    /// `min_size` larger than the size of the request is clamped to it.
+    pub fn fit_size(
+        &self,
+        request: &text::MeasureRequest<'_>,
+        wrap: primitive::Wrap,
+        min_size: f32,
+    ) -> f32 {
+        self.text_pipeline.fit_size(request, wrap, min_size)
+    }
+
+    /// Measures the given [`primitive::Text`] and returns its
+    /// [`text::Metrics`].
+    ///
+    /// Unlike [`Backend::measure_text`], this includes the vertical metrics
+    /// of the font, which can be used to align other content to the baseline
+    /// of the text.
+    pub fn measure_detailed(&self, text: &primitive::Text) -> text::Metrics {
+        self.text_pipeline
+            .measure_detailed(&layer::Text::from(text))
+    }
+
+    /// Returns the bounds of every glyph of the given [`primitive::Text`]
+    /// once laid out, relative to the top-left corner of the text.
+    ///
+    /// The bounds are returned in visual order and they span the whole height
+    /// of their line.
+    pub fn glyph_rects(&self, text: &primitive::Text) -> Vec<Rectangle> {
+        self.text_pipeline.glyph_rects(&layer::Text::from(text))
+    }
+
+    /// Tests whether the provided point is within the boundaries of the given
+    /// [`primitive::Text`], returning information about the nearest character.
+    pub fn hit_test_text(
+        &self,
+        text: &primitive::Text,
+        point: Point,
+        nearest_only: bool,
+    ) -> Option<core::text::Hit> {
+        self.text_pipeline.hit_test_text(
+            &layer::Text::from(text),
+            point,
+            nearest_only,
+        )
+    }
+
+    /// Returns the caret of the given byte index of a [`text::MeasureRequest`]
+    /// once laid out.
+    ///
+    /// The caret is a rectangle with no width placed at the leading edge of
+    /// the character at the index, spanning the height of its line. The end
+    /// of a line and the end of the text are valid indices.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn caret_rect(
+        &self,
+        request: &text::MeasureRequest<'_>,
+        index: usize,
+    ) -> Option<Rectangle> {
+        self.text_pipeline.caret_rect(request, index)
+    }
+
+    /// Returns the width of the line of the given content that contains the
+    /// given byte index, from its start up to the index, once laid out with
+    /// no bounds.
+    ///
+    /// The index is clamped to the end of its line and snapped to the start
+    /// of its grapheme cluster. Only that line is laid out, so this is
+    /// cheaper than [`Backend::caret_rect`] when only the advance is needed,
+    /// like when placing a completion popup.
+    pub fn width_to_index(
+        &self,
+        content: &str,
+        size: f32,
+        font: Font,
+        shaping: core::text::Shaping,
+        index: usize,
+    ) -> f32 {
+        self.text_pipeline
+            .width_to_index(content, size, font, shaping, index)
+    }
+
+    /// Returns the bounds of the given [`primitive::Text`] once laid out and
+    /// aligned, in logical pixels.
+    ///
+    /// These are the bounds the text is drawn in, which can be used for hit
+    /// regions or focus rings. The `scale_factor` only matters for text with
+    /// a [`primitive::Text::scale_override`].
+    pub fn text_bounds(
+        &self,
+        text: &primitive::Text,
+        scale_factor: f32,
+    ) -> Rectangle {
+        self.text_pipeline
+            .bounds_of(&layer::Text::from(text), scale_factor)
+    }
+
+    /// Returns a quad [`Primitive`] for the caret of the given byte index of a
+    /// [`primitive::Text`], with the given width and [`Color`].
+    ///
+    /// The caret is positioned like [`Backend::caret_rect`], but relative to
+    /// the bounds and alignment the text is drawn with. Since it is a separate
+    /// primitive, blinking or moving it never invalidates the cached buffer
+    /// of the text.
+    ///
+    /// Like in [`Backend::text_bounds`], the `scale_factor` only matters for
+    /// text with a [`primitive::Text::scale_override`].
+    pub fn caret_primitive(
+        &self,
+        text: &primitive::Text,
+        index: usize,
+        width: f32,
+        color: Color,
+        scale_factor: f32,
+    ) -> Option<Primitive> {
+        let caret = self.text_pipeline.text_caret(
+            &layer::Text::from(text),
+            index,
+            scale_factor,
+        )?;
+
+        Some(Primitive::Quad {
+            bounds: Rectangle {
+                x: caret.x - width / 2.0,
+                width,
+                ..caret
+            },
+            background: core::Background::Color(color),
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        })
+    }
+
+    /// Tests whether the provided point is within the boundaries of the given
+    /// [`primitive::Text`], returning the [`text::HitDetails`] of the nearest
+    /// character.
+    pub fn hit_test_detailed(
+        &self,
+        text: &primitive::Text,
+        point: Point,
+        nearest_only: bool,
+    ) -> Option<text::HitDetails> {
+        self.text_pipeline.hit_test_detailed(
+            &layer::Text::from(text),
+            point,
+            nearest_only,
+        )
+    }
+
+    /// Loads a font from its bytes, returning a [`text::FontHandle`] that
+    /// can be used to unload it later with [`Backend::unload_font`].
+    pub fn load_font_with_handle(
+        &mut self,
+        bytes: Cow<'static, [u8]>,
+    ) -> Result<text::FontHandle, core::font::Error> {
+        self.text_pipeline.load_font(bytes)
+    }
+
+    /// Calls the given closure with the [`glyphon::FontSystem`] of the text
+    /// pipeline.
+    ///
+    /// This can be used to load fonts from custom sources or to shape text
+    /// with the same fonts as the [`Backend`]. The text cache is cleared
+    /// afterwards, since the available fonts may have changed.
+    pub fn with_font_system<T>(
+        &mut self,
+        f: impl FnOnce(&mut glyphon::FontSystem) -> T,
+    ) -> T {
+        self.text_pipeline.with_font_system(f)
+    }
+
+    /// Unloads the faces of a font previously loaded with
+    /// [`Backend::load_font_with_handle`].
+    ///
+    /// Any cached text that was shaped using these faces is invalidated.
+    pub fn unload_font(&mut self, handle: text::FontHandle) {
+        self.text_pipeline.unload_font(handle);
+    }
+
+    /// Returns the distinct names of all the font families currently loaded,
+    /// sorted alphabetically.
+    pub fn available_font_families(&self) -> Vec<String> {
+        self.text_pipeline.available_families()
+    }
+
+    /// Returns true if a font family with the given name is loaded.
+    ///
+    /// This can be used to fall back gracefully before using a
+    /// [`Font`] with a [`core::font::Family::Name`] that may be missing.
+    pub fn has_font_family(&self, name: &str) -> bool {
+        self.text_pipeline.has_family(name)
+    }
+
+    /// Shapes the given [`primitive::Text`]s and uploads their glyphs to the
+    /// text atlas ahead of time.
+    ///
+    /// This can be used during a loading screen to avoid a hitch on the first
+    /// frame that draws some known text. The `scale_factor` must match the
+    /// one that will be used to draw it.
+    ///
+    /// Preheated text survives any number of frames until it is drawn for
+    /// the first time, and is trimmed as usual afterwards. It is still
+    /// dropped when the text cache is cleared, invalidated or over its
+    /// capacity or budget.
+    pub fn preheat_text(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texts: &[primitive::Text],
+        scale_factor: f32,
+    ) {
+        let sections: Vec<_> = texts.iter().map(layer::Text::from).collect();
+
+        self.text_pipeline
+            .preheat(device, queue, &sections, scale_factor);
+    }
+
+    /// Rasterizes the glyphs of the given characters and uploads them to the
+    /// text atlas ahead of time.
+    ///
+    /// Unlike [`Backend::preheat_text`], this only needs a set of characters
+    /// instead of the full text, like the digits of a clock. The glyphs may
+    /// still be evicted from the atlas if it runs out of space later on.
+    ///
+    /// The glyphs are rasterized at `size * scale_factor` physical pixels,
+    /// the same size used when drawing text with that `scale_factor`. Any
+    /// scale override of the text that is drawn later must be passed as the
+    /// `scale_factor` for the warmed glyphs to be reused.
+    pub fn warm_glyphs(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chars: &str,
+        size: f32,
+        font: Font,
+        scale_factor: f32,
+    ) {
+        self.text_pipeline.warm_glyphs(
+            device,
+            queue,
+            chars,
+            size,
+            font,
+            scale_factor,
+        );
+    }
+
+    /// Drops every buffer in the text cache at once.
+    ///
+    /// Any text drawn or measured afterwards is shaped again, which can be
+    /// useful after a change that affects how fonts are resolved. Buffers
+    /// pinned with [`Backend::pin_text`] are kept, and shaped again right
+    /// away.
+    pub fn clear_text_cache(&mut self) {
+        self.text_pipeline.clear_cache();
+    }
+
+    /// Draws the given [`primitive::Text`] into a new texture of the given
+    /// physical size, recording the commands in the given encoder.
+    ///
+    /// The text is drawn at the origin of the texture, over a transparent
+    /// background. Its background, decorations, selections and shadow are
+    /// not drawn.
+    ///
+    /// The texture can then be reused as an image, trading the work of
+    /// drawing glyphs every frame for a single textured quad.
+    ///
+    /// The texture has the format of the [`Backend`] and it can be sampled,
+    /// which also makes it the starting point to mask text with an arbitrary
+    /// shape. A custom pass can sample both this texture and a mask texture
+    /// of the same size, multiplying the alpha of the text by the mask
+    /// before blending the result into the frame.
+    pub fn render_text_to_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        text: &primitive::Text,
+        size: Size<u32>,
+        scale_factor: f32,
+    ) -> wgpu::Texture {
+        self.text_pipeline.render_to_texture(
+            device,
+            queue,
+            encoder,
+            &layer::Text::from(text),
+            size,
+            scale_factor,
+        )
+    }
+
+    /// Shapes the given [`primitive::Text`] and pins its buffer in the text
+    /// cache until [`Backend::unpin_text`] is called.
+    ///
+    /// The returned [`text::Handle`] can be set on the same
+    /// [`primitive::Text`] to skip hashing its contents every frame, which
+    /// is useful for large and static text.
+    pub fn pin_text(&mut self, text: &primitive::Text) -> text::Handle {
+        self.text_pipeline.pin(&layer::Text::from(text))
+    }
+
+    /// Appends some content to a buffer pinned with [`Backend::pin_text`].
+    ///
+    /// This is useful for long-lived text that keeps growing, like the output
+    /// of a console. Only the lines that changed are shaped again, unless the
+    /// text is truncated or colored as a whole, and the content is appended
+    /// with the shaping of the pinned text. The pinned buffer owns all of its
+    /// content, so the [`primitive::Text`] drawn with the [`text::Handle`]
+    /// does not need to be kept in sync.
+    ///
+    /// Returns whether the buffer was still pinned in the text cache.
+    pub fn append_text(
+        &mut self,
+        handle: text::Handle,
+        content: &str,
+        font: Font,
+    ) -> bool {
+        self.text_pipeline.append(handle, content, font)
+    }
+
+    /// Unpins a buffer pinned with [`Backend::pin_text`], dropping it from
+    /// the text cache.
+    ///
+    /// Any [`primitive::Text`] still drawn with its [`text::Handle`] is
+    /// shaped from its own contents again.
+    pub fn unpin_text(&mut self, handle: text::Handle) {
+        self.text_pipeline.unpin(handle);
+    }
+
+    /// Drops the cached buffer of the given [`primitive::Text`], if any.
+    ///
+    /// Unlike [`Backend::clear_text_cache`], any other cached text is kept.
+    /// Returns whether a buffer was actually dropped.
+    pub fn invalidate_text(&mut self, text: &primitive::Text) -> bool {
+        self.text_pipeline.invalidate(&layer::Text::from(text))
+    }
+
+    /// Evicts the least recently used buffers of the text cache until its
+    /// estimated memory usage fits in the given amount of bytes.
+    pub fn trim_text_cache(&mut self, max_bytes: usize) {
+        self.text_pipeline.trim_to_budget(max_bytes);
+    }
+
+    /// Marks the glyphs of the text atlas as unused, so they can be evicted
+    /// once the atlas runs out of space.
+    ///
+    /// This already happens at the end of every frame. Calling it in between
+    /// frames is only useful to render loops that draw multiple independent
+    /// scenes.
+    pub fn trim_text_atlas(&mut self) {
+        self.text_pipeline.trim_atlas();
+    }
+
+    /// Drops every cached text buffer that has not been drawn nor measured
+    /// since the last trim.
+    ///
+    /// Like [`Backend::trim_text_atlas`], this already happens at the end of
+    /// every frame.
+    pub fn trim_text_render_cache(&mut self) {
+        self.text_pipeline.trim_render_cache();
+    }
+
+    /// Sets the [`Color`] used to draw any text with a fully transparent
+    /// color.
+    ///
+    /// This makes it possible to resolve a theme color in a single place,
+    /// instead of for every piece of text. By default, it is `None` and
+    /// transparent text stays invisible.
+    pub fn set_default_text_color(&mut self, color: Option<Color>) {
+        self.text_pipeline.set_default_color(color);
+    }
+
+    /// Returns the amount of times the glyph atlas of the text pipeline was
+    /// full and could not grow any further.
+    ///
+    /// Text prepared when this happens may be drawn with missing glyphs.
+    pub fn text_atlas_overflows(&self) -> u64 {
+        self.text_pipeline.atlas_overflows()
+    }
+
+    /// Sets a callback that is called every time the glyph atlas of the text
+    /// pipeline is full and cannot grow any further, or removes it.
+    ///
+    /// This can be used to react to an exhausted atlas, like by dropping
+    /// low-priority text or lowering the scale factor, instead of drawing
+    /// text with missing glyphs. The text is still prepared as before.
+    ///
+    /// The kind of content that did not fit and the size of the atlas are
+    /// not known, since glyphon does not expose them, so only the amount of
+    /// text sections that were being prepared is given.
+    pub fn on_text_atlas_exhausted(
+        &mut self,
+        callback: Option<Box<dyn FnMut(text::Exhaustion) + Send>>,
+    ) {
+        self.text_pipeline.on_exhaustion(callback);
+    }
+
+    /// Returns the [`text::Preparation`] of the text of the last frame.
+    ///
+    /// This can be used to drop or simplify text in the next frame once the
+    /// glyph atlas is exhausted.
+    pub fn text_preparation(&self) -> text::Preparation {
+        self.text_preparation
+    }
+
+    /// Returns the [`text::CacheStats`] of the text pipeline.
+    ///
+    /// This can be useful to find out whether some text is constantly
+    /// missing the cache; for instance, because its bounds keep changing.
+    pub fn text_cache_stats(&self) -> text::CacheStats {
+        self.text_pipeline.cache_stats()
+    }
+
     fn prepare(
         &mut self,
         device: &wgpu::Device,
@@ -177,14 +721,19 @@ impl Backend {
             }
 
             if !layer.text.is_empty() {
-                self.text_pipeline.prepare(
+                let preparation = self.text_pipeline.prepare(
                     device,
                     queue,
                     &layer.text,
                     layer.bounds,
+                    transformation,
                     scale_factor,
                     target_size,
                 );
+
+                if preparation == text::Preparation::AtlasExhausted {
+                    self.text_preparation = preparation;
+                }
             }
         }
     }
@@ -374,8 +923,11 @@ impl backend::Text for Backend {
         )
     }
 
-    fn load_font(&mut self, font: Cow<'static, [u8]>) {
-        self.text_pipeline.load_font(font);
+    fn load_font(
+        &mut self,
+        font: Cow<'static, [u8]>,
+    ) -> Result<(), core::font::Error> {
+        self.text_pipeline.load_font(font).map(|_| ())
     }
 }
 
@@ -58,6 +58,7 @@ impl<'a> Layer<'a> {
         for (i, line) in lines.iter().enumerate() {
             let text = Text {
                 content: line.as_ref(),
+                spans: &[],
                 bounds: Rectangle::new(
                     Point::new(11.0, 11.0 + 25.0 * i as f32),
                     Size::INFINITY,
@@ -69,6 +70,25 @@ impl<'a> Layer<'a> {
                 horizontal_alignment: alignment::Horizontal::Left,
                 vertical_alignment: alignment::Vertical::Top,
                 shaping: core::text::Shaping::Basic,
+                decoration: primitive::Decoration::None,
+                ellipsis: false,
+                max_lines: None,
+                clamp: primitive::LineClamp::Ellipsis,
+                wrap: primitive::Wrap::Word,
+                gradient: None,
+                shadow: None,
+                stroke: None,
+                background: None,
+                selections: &[],
+                opacity: 1.0,
+                scale_override: None,
+                handle: None,
+                clip_bounds: None,
+                pixel_snap: false,
+                content_id: None,
+                language: None,
+                tab_width: None,
+                paragraph_spacing: 0.0,
             };
 
             overlay.text.push(text);
@@ -128,6 +148,7 @@ impl<'a> Layer<'a> {
 
                 layer.text.push(Text {
                     content,
+                    spans: &[],
                     bounds: *bounds + translation,
                     size: *size,
                     line_height: *line_height,
@@ -136,6 +157,25 @@ impl<'a> Layer<'a> {
                     horizontal_alignment: *horizontal_alignment,
                     vertical_alignment: *vertical_alignment,
                     shaping: *shaping,
+                    decoration: primitive::Decoration::None,
+                    ellipsis: false,
+                    max_lines: None,
+                    clamp: primitive::LineClamp::Ellipsis,
+                    wrap: primitive::Wrap::Word,
+                    gradient: None,
+                    shadow: None,
+                    stroke: None,
+                    background: None,
+                    selections: &[],
+                    opacity: 1.0,
+                    scale_override: None,
+                    handle: None,
+                    clip_bounds: None,
+                    pixel_snap: false,
+                    content_id: None,
+                    language: None,
+                    tab_width: None,
+                    paragraph_spacing: 0.0,
                 });
             }
             Primitive::Quad {
@@ -277,6 +317,17 @@ impl<'a> Layer<'a> {
                         }
                     }
                 },
+                primitive::Custom::Text(text) => {
+                    let layer = &mut layers[current_layer];
+
+                    layer.text.push(Text {
+                        bounds: text.bounds + translation,
+                        clip_bounds: text
+                            .clip_bounds
+                            .map(|clip_bounds| clip_bounds + translation),
+                        ..Text::from(text)
+                    });
+                }
             },
         }
     }
@@ -1,6 +1,7 @@
 use crate::core::alignment;
 use crate::core::text;
-use crate::core::{Color, Font, Rectangle};
+use crate::core::{Color, Font, Gradient, Rectangle};
+use crate::primitive;
 
 /// A paragraph of text.
 #[derive(Debug, Clone, Copy)]
@@ -8,6 +9,9 @@ pub struct Text<'a> {
     /// The content of the [`Text`].
     pub content: &'a str,
 
+    /// The styled spans of the [`Text`].
+    pub spans: &'a [primitive::Span],
+
     /// The layout bounds of the [`Text`].
     pub bounds: Rectangle,
 
@@ -31,4 +35,98 @@ pub struct Text<'a> {
 
     /// The shaping strategy of the text.
     pub shaping: text::Shaping,
+
+    /// The line decoration of the text.
+    pub decoration: primitive::Decoration,
+
+    /// Whether the text should be truncated with an ellipsis.
+    pub ellipsis: bool,
+
+    /// The maximum amount of lines of the text, if any.
+    pub max_lines: Option<usize>,
+
+    /// How the last visible line of the text ends when truncated.
+    pub clamp: primitive::LineClamp,
+
+    /// The wrapping strategy of the text.
+    pub wrap: primitive::Wrap,
+
+    /// The gradient used to fill the glyphs of the text, if any.
+    pub gradient: Option<Gradient>,
+
+    /// The drop shadow of the text, if any.
+    pub shadow: Option<primitive::Shadow>,
+
+    /// The outline of the glyphs of the text, if any.
+    pub stroke: Option<primitive::Stroke>,
+
+    /// The color filling the bounds of the text, if any.
+    pub background: Option<Color>,
+
+    /// The selections highlighted behind the glyphs of the text.
+    pub selections: &'a [primitive::Selection],
+
+    /// The opacity of the text.
+    pub opacity: f32,
+
+    /// The scale factor used to draw the text instead of the one of the
+    /// target, if any.
+    pub scale_override: Option<f32>,
+
+    /// The pinned buffer of the text, if any.
+    pub handle: Option<crate::text::Handle>,
+
+    /// The bounds the text is clipped to, if any.
+    pub clip_bounds: Option<Rectangle>,
+
+    /// Whether the position of the text is snapped to whole physical pixels.
+    pub pixel_snap: bool,
+
+    /// An identifier of the content of the text, if any.
+    pub content_id: Option<u64>,
+
+    /// The language of the content of the text, if any.
+    pub language: Option<&'static str>,
+
+    /// The width of a tab stop of the text, in spaces, if any.
+    pub tab_width: Option<usize>,
+
+    /// The vertical space between the paragraphs of the text.
+    pub paragraph_spacing: f32,
+}
+
+impl<'a> From<&'a primitive::Text> for Text<'a> {
+    fn from(text: &'a primitive::Text) -> Self {
+        Self {
+            content: &text.content,
+            spans: &text.spans,
+            bounds: text.bounds,
+            color: text.color,
+            size: text.size,
+            line_height: text.line_height,
+            font: text.font,
+            horizontal_alignment: text.horizontal_alignment,
+            vertical_alignment: text.vertical_alignment,
+            shaping: text.shaping,
+            decoration: text.decoration,
+            ellipsis: text.ellipsis,
+            max_lines: text.max_lines,
+            clamp: text.clamp,
+            wrap: text.wrap,
+            gradient: text.gradient,
+            shadow: text.shadow,
+            stroke: text.stroke,
+            background: text.background,
+            selections: &text.selections,
+            opacity: text.opacity,
+            scale_override: text.scale_override,
+            handle: text.handle,
+            clip_bounds: text.clip_bounds,
+            pixel_snap: text.pixel_snap,
+            content_id: text.content_id,
+            language: text.language,
+            tab_width: text.tab_width,
+            paragraph_spacing: text.paragraph_spacing,
+        }
+    }
 }
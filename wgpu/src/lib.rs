@@ -37,6 +37,7 @@
 pub mod layer;
 pub mod primitive;
 pub mod settings;
+pub mod text;
 pub mod window;
 
 #[cfg(feature = "geometry")]
@@ -46,7 +47,6 @@ mod backend;
 mod buffer;
 mod color;
 mod quad;
-mod text;
 mod triangle;
 
 use buffer::Buffer;
@@ -54,6 +54,7 @@ use buffer::Buffer;
 pub use iced_graphics as graphics;
 pub use iced_graphics::core;
 
+pub use glyphon;
 pub use wgpu;
 
 pub use backend::Backend;
@@ -1,4 +1,10 @@
 //! Draw using different graphical primitives.
+mod text;
+
+pub use text::{
+    Decoration, Line, LineClamp, Selection, Shadow, Span, Stroke, Text, Wrap,
+};
+
 use crate::core::Rectangle;
 use crate::graphics::{Damage, Mesh};
 
@@ -10,12 +16,15 @@ pub type Primitive = crate::graphics::Primitive<Custom>;
 pub enum Custom {
     /// A mesh primitive.
     Mesh(Mesh),
+    /// A text primitive with styled spans.
+    Text(Text),
 }
 
 impl Damage for Custom {
     fn bounds(&self) -> Rectangle {
         match self {
             Self::Mesh(mesh) => mesh.bounds(),
+            Self::Text(text) => text.bounds(),
         }
     }
 }
@@ -0,0 +1,390 @@
+use crate::core::alignment;
+use crate::core::text::{LineHeight, Shaping};
+use crate::core::{Color, Font, Gradient, Rectangle, Size, Vector};
+use crate::graphics::Damage;
+use crate::text::Handle;
+
+use std::ops::Range;
+
+/// A paragraph of text that can be styled in ranges.
+///
+/// Unlike a regular text primitive, the [`Span`]s of a [`Text`] are shaped
+/// and wrapped together as a single paragraph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    /// The contents of the [`Text`].
+    pub content: String,
+
+    /// The styled [`Span`]s of the [`Text`].
+    ///
+    /// Any part of the contents not covered by a [`Span`] uses the default
+    /// style of the [`Text`].
+    pub spans: Vec<Span>,
+
+    /// The layout bounds of the [`Text`].
+    pub bounds: Rectangle,
+
+    /// The default color of the [`Text`].
+    pub color: Color,
+
+    /// The size of the [`Text`] in logical pixels.
+    pub size: f32,
+
+    /// The line height of the [`Text`].
+    pub line_height: LineHeight,
+
+    /// The default font of the [`Text`].
+    pub font: Font,
+
+    /// The horizontal alignment of the [`Text`].
+    pub horizontal_alignment: alignment::Horizontal,
+
+    /// The vertical alignment of the [`Text`].
+    pub vertical_alignment: alignment::Vertical,
+
+    /// The shaping strategy of the [`Text`].
+    pub shaping: Shaping,
+
+    /// The [`Decoration`] of the [`Text`].
+    pub decoration: Decoration,
+
+    /// Whether the [`Text`] should be truncated to a single line ending with
+    /// an ellipsis when it does not fit its bounds.
+    pub ellipsis: bool,
+
+    /// The maximum amount of lines of the [`Text`], if any.
+    ///
+    /// The last visible line is clamped according to [`Text::clamp`] when
+    /// the [`Text`] is truncated.
+    pub max_lines: Option<usize>,
+
+    /// How the last visible line of a [`Text`] truncated by
+    /// [`Text::max_lines`] ends.
+    pub clamp: LineClamp,
+
+    /// The [`Wrap`] strategy of the [`Text`].
+    pub wrap: Wrap,
+
+    /// The [`Gradient`] used to fill the glyphs of the [`Text`], if any.
+    ///
+    /// The [`Gradient`] spans the laid out contents of the [`Text`] and it
+    /// takes precedence over the colors of the [`Text`] and its [`Span`]s.
+    pub gradient: Option<Gradient>,
+
+    /// The [`Shadow`] drawn behind the [`Text`], if any.
+    pub shadow: Option<Shadow>,
+
+    /// The [`Stroke`] outlining the glyphs of the [`Text`], if any.
+    pub stroke: Option<Stroke>,
+
+    /// The color filling the aligned bounds of the [`Text`], if any.
+    ///
+    /// It is drawn behind any [`Selection`].
+    pub background: Option<Color>,
+
+    /// The [`Selection`]s highlighted behind the glyphs of the [`Text`].
+    pub selections: Vec<Selection>,
+
+    /// The opacity of the [`Text`], multiplying the alpha of its color.
+    ///
+    /// The colors of any [`Span`] or [`Gradient`] fade with it as well.
+    pub opacity: f32,
+
+    /// The scale factor used to draw the [`Text`] instead of the one of the
+    /// target, if any.
+    ///
+    /// The position of the [`Text`] is still scaled with the scale factor of
+    /// the target, but its glyphs and bounds use the overridden one.
+    pub scale_override: Option<f32>,
+
+    /// The [`Handle`] of a buffer pinned ahead of time for this [`Text`], if
+    /// any.
+    ///
+    /// A valid [`Handle`] skips hashing the contents of the [`Text`] when it
+    /// is drawn. The [`Handle`] must have been obtained from this exact
+    /// [`Text`], as any change to it will not be noticed.
+    pub handle: Option<Handle>,
+
+    /// The bounds the [`Text`] is clipped to, if any.
+    ///
+    /// They are applied on top of the clip bounds of the layer, which lets
+    /// sections with different clips be drawn in the same layer.
+    pub clip_bounds: Option<Rectangle>,
+
+    /// Whether the position of the [`Text`] is snapped to whole physical
+    /// pixels.
+    ///
+    /// Snapping keeps glyphs crisp at fractional scale factors, but it makes
+    /// animated text move in steps. By default, it is `false`.
+    pub pixel_snap: bool,
+
+    /// An identifier of the contents of the [`Text`], if any.
+    ///
+    /// When set, the text cache uses it instead of hashing the contents,
+    /// which is faster for long documents. The identifier must change
+    /// whenever the contents do, like the revision of a rope.
+    pub content_id: Option<u64>,
+
+    /// The language of the contents of the [`Text`], as a BCP 47 tag like
+    /// `"ja"` or `"zh-Hans"`, if any.
+    ///
+    /// Text in different languages is cached separately, even with the same
+    /// contents. However, cosmic-text 0.9 cannot pass a language to the
+    /// shaper yet, so shared ideographs are still shaped the same way.
+    pub language: Option<&'static str>,
+
+    /// The width of a tab stop of the [`Text`], in spaces, if any.
+    ///
+    /// When set, every tab is expanded to the next tab stop before shaping.
+    /// Otherwise, tabs are shaped like any other character.
+    pub tab_width: Option<usize>,
+
+    /// The vertical space between the paragraphs of the [`Text`], in
+    /// logical pixels.
+    ///
+    /// A paragraph ends at a blank line, so single line breaks are laid out
+    /// without any extra space.
+    pub paragraph_spacing: f32,
+}
+
+impl Default for Text {
+    fn default() -> Text {
+        Text {
+            content: String::new(),
+            spans: Vec::new(),
+            bounds: Rectangle::with_size(Size::INFINITY),
+            color: Color::BLACK,
+            size: 16.0,
+            line_height: LineHeight::default(),
+            font: Font::default(),
+            horizontal_alignment: alignment::Horizontal::Left,
+            vertical_alignment: alignment::Vertical::Top,
+            shaping: Shaping::Basic,
+            decoration: Decoration::None,
+            ellipsis: false,
+            max_lines: None,
+            clamp: LineClamp::Ellipsis,
+            wrap: Wrap::Word,
+            gradient: None,
+            shadow: None,
+            stroke: None,
+            background: None,
+            selections: Vec::new(),
+            opacity: 1.0,
+            scale_override: None,
+            handle: None,
+            clip_bounds: None,
+            pixel_snap: false,
+            content_id: None,
+            language: None,
+            tab_width: None,
+            paragraph_spacing: 0.0,
+        }
+    }
+}
+
+impl Damage for Text {
+    fn bounds(&self) -> Rectangle {
+        let mut bounds = self.bounds;
+
+        bounds.x = match self.horizontal_alignment {
+            alignment::Horizontal::Left | alignment::Horizontal::Justify => {
+                bounds.x
+            }
+            alignment::Horizontal::Center => bounds.x - bounds.width / 2.0,
+            alignment::Horizontal::Right => bounds.x - bounds.width,
+        };
+
+        bounds.y = match self.vertical_alignment {
+            alignment::Vertical::Top => bounds.y,
+            alignment::Vertical::Center => bounds.y - bounds.height / 2.0,
+            alignment::Vertical::Bottom => bounds.y - bounds.height,
+            alignment::Vertical::Baseline => bounds.y - bounds.height,
+        };
+
+        // The baseline is only known once laid out, so the text may be on
+        // either side of it
+        if self.vertical_alignment == alignment::Vertical::Baseline {
+            bounds.height *= 2.0;
+        }
+
+        if let Some(stroke) = self.stroke {
+            bounds = bounds.expand(stroke.width.max(0.0));
+        }
+
+        if let Some(shadow) = self.shadow {
+            bounds = bounds
+                .union(&(bounds + shadow.offset).expand(shadow.blur.max(0.0)));
+        }
+
+        let bounds = bounds.expand(1.5);
+
+        match self.clip_bounds {
+            Some(clip_bounds) => {
+                bounds.intersection(&clip_bounds).unwrap_or(Rectangle {
+                    width: 0.0,
+                    height: 0.0,
+                    ..bounds
+                })
+            }
+            None => bounds,
+        }
+    }
+}
+
+/// A highlighted range of the contents of a [`Text`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    /// The range of bytes of the contents that is selected.
+    pub range: Range<usize>,
+
+    /// The background color of the [`Selection`].
+    pub color: Color,
+}
+
+/// The outline of the glyphs of a [`Text`].
+///
+/// The outline is approximated by drawing copies of the [`Text`] around it,
+/// which works best for thin outlines. Like a [`Shadow`], every copy is drawn
+/// with the color of the [`Stroke`], even for any [`Span`] with its own
+/// color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    /// The color of the [`Stroke`].
+    pub color: Color,
+
+    /// The width of the [`Stroke`], in logical pixels.
+    pub width: f32,
+}
+
+/// The drop shadow of a [`Text`].
+///
+/// The [`Shadow`] reuses the laid out glyphs of the [`Text`], but draws every
+/// one of them with the color of the [`Shadow`], including the glyphs of any
+/// [`Span`] with its own color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    /// The color of the [`Shadow`].
+    pub color: Color,
+
+    /// The offset of the [`Shadow`] relative to the [`Text`].
+    pub offset: Vector,
+
+    /// The blur radius of the [`Shadow`].
+    ///
+    /// Glyphs are not actually blurred. Instead, a non-zero blur is
+    /// approximated by drawing a few translucent copies of the [`Shadow`]
+    /// spread around its offset.
+    pub blur: f32,
+}
+
+/// The line decoration of a [`Text`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Decoration {
+    /// No decoration.
+    #[default]
+    None,
+
+    /// A line below the baseline of every line of text.
+    Underline(Line),
+
+    /// A line through the middle of every line of text.
+    Strikethrough(Line),
+}
+
+/// The geometry of the line of a [`Decoration`].
+///
+/// Any value left as `None` is derived from the metrics of the font.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Line {
+    /// The thickness of the line, in logical pixels.
+    pub thickness: Option<f32>,
+
+    /// The distance from the baseline to the top of the line, in logical
+    /// pixels. Positive values move the line down.
+    pub offset: Option<f32>,
+
+    /// Whether the line extends below the trailing whitespace of every line
+    /// of text.
+    ///
+    /// By default, it is `false` and the line ends at the last visible
+    /// glyph, which looks best for links.
+    pub trailing_whitespace: bool,
+}
+
+/// How the last visible line of a truncated [`Text`] ends.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineClamp {
+    /// The line ends with an ellipsis.
+    #[default]
+    Ellipsis,
+
+    /// The glyphs of the line fade out over the given width at its end.
+    ///
+    /// The fade is computed with the color of the [`Text`] when it is laid
+    /// out, so any default text color of the [`Backend`] is not applied to
+    /// it.
+    ///
+    /// [`Backend`]: crate::Backend
+    Fade {
+        /// The width of the fade, in logical pixels.
+        width: f32,
+    },
+}
+
+/// The wrapping strategy of a [`Text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Wrap {
+    /// Lines are wrapped at word boundaries.
+    #[default]
+    Word,
+
+    /// Lines are wrapped at any glyph.
+    Glyph,
+
+    /// Lines are never wrapped.
+    None,
+}
+
+/// A range of the contents of a [`Text`] with its own style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    /// The byte range of the contents styled by the [`Span`].
+    pub range: Range<usize>,
+
+    /// The font of the [`Span`], if different from the one of the [`Text`].
+    pub font: Option<Font>,
+
+    /// The color of the [`Span`], if different from the one of the [`Text`].
+    pub color: Option<Color>,
+}
+
+impl Span {
+    /// Creates a new [`Span`] for the given byte range, using the default
+    /// style of its [`Text`].
+    pub fn new(range: Range<usize>) -> Self {
+        Self {
+            range,
+            font: None,
+            color: None,
+        }
+    }
+
+    /// Sets the font of the [`Span`].
+    pub fn font(mut self, font: Font) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    /// Sets the color of the [`Span`].
+    pub fn color(mut self, color: impl Into<Color>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+}
+
+impl From<(Range<usize>, Color)> for Span {
+    fn from((range, color): (Range<usize>, Color)) -> Self {
+        Span::new(range).color(color)
+    }
+}
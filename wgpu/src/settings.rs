@@ -1,6 +1,7 @@
 //! Configure a renderer.
 use crate::core::Font;
 use crate::graphics::Antialiasing;
+use crate::text;
 
 /// The settings of a [`Backend`].
 ///
@@ -27,6 +28,50 @@ pub struct Settings {
     ///
     /// By default, it is `None`.
     pub antialiasing: Option<Antialiasing>,
+
+    /// The maximum amount of text buffers kept in the text cache.
+    ///
+    /// When set, the least recently used buffers are evicted once the cache
+    /// exceeds this amount. By default, it is `None`.
+    pub text_cache_capacity: Option<usize>,
+
+    /// The maximum amount of memory the text cache should use, in bytes.
+    ///
+    /// The memory used by a text buffer is estimated from its contents and
+    /// its glyphs. When set, the least recently used buffers are evicted once
+    /// the budget is exceeded, even if they were used recently. By default,
+    /// it is `None`.
+    pub text_cache_budget: Option<usize>,
+
+    /// The seed used to hash the keys of the text cache.
+    ///
+    /// When set, the text cache behaves deterministically, which can be
+    /// useful for reproducible tests. By default, it is `None` and a random
+    /// seed is used.
+    pub text_cache_seed: Option<u64>,
+
+    /// The hashers used to key the text cache, if different from the
+    /// default ones.
+    ///
+    /// When set, the `text_cache_seed` is ignored. By default, it is `None`.
+    pub text_cache_hasher: Option<text::CacheHasher>,
+
+    /// Whether the fonts installed in the system can be used to draw text.
+    ///
+    /// When disabled, only the fonts explicitly loaded are available and
+    /// font fallback uses a fixed locale, so text measurements do not depend
+    /// on the machine. This can be useful for snapshot tests of layouts. By
+    /// default, it is `true`.
+    pub text_system_fonts: bool,
+
+    /// The character drawn in place of any character that no loaded font
+    /// can display.
+    ///
+    /// When set, characters that would be drawn as the empty box of a
+    /// missing glyph are drawn with it instead, like `'?'`, as long as it is
+    /// not missing itself. The content of the text stays the same. By
+    /// default, it is `None`.
+    pub text_missing_glyph: Option<char>,
 }
 
 impl Settings {
@@ -61,6 +106,12 @@ impl Default for Settings {
             default_font: Font::default(),
             default_text_size: 16.0,
             antialiasing: None,
+            text_cache_capacity: None,
+            text_cache_budget: None,
+            text_cache_seed: None,
+            text_cache_hasher: None,
+            text_system_fonts: true,
+            text_missing_glyph: None,
         }
     }
 }
@@ -1,39 +1,95 @@
+//! Inspect the text pipeline of a [`Backend`].
+//!
+//! [`Backend`]: crate::Backend
 use crate::core::alignment;
 use crate::core::font::{self, Font};
+use crate::core::gradient;
 use crate::core::text::{Hit, LineHeight, Shaping};
-use crate::core::{Pixels, Point, Rectangle, Size};
+use crate::core::{
+    Background, Color, Gradient, Pixels, Point, Rectangle, Size, Vector,
+};
 use crate::graphics::color;
+use crate::graphics::Transformation;
 use crate::layer::Text;
+use crate::primitive;
+use crate::quad::{self, Quad};
+use crate::Settings;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::hash_map;
+use std::fmt;
 use std::hash::{BuildHasher, Hash, Hasher};
 use std::sync::Arc;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[allow(missing_debug_implementations)]
-pub struct Pipeline {
+pub(crate) struct Pipeline {
     font_system: RefCell<glyphon::FontSystem>,
     renderers: Vec<glyphon::TextRenderer>,
     atlas: glyphon::TextAtlas,
+    swash_cache: glyphon::SwashCache,
+    format: wgpu::TextureFormat,
+    decoration_pipeline: quad::Pipeline,
+    decorations: Vec<Option<Decorations>>,
     prepare_layer: usize,
+    decoration_layer: usize,
+    atlas_overflows: u64,
+    on_exhaustion: Option<Box<dyn FnMut(Exhaustion) + Send>>,
+    default_color: Option<Color>,
     cache: RefCell<Cache>,
 }
 
+struct Decorations {
+    layer: usize,
+    quads: quad::Batch,
+}
+
 impl Pipeline {
     pub fn new(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         format: wgpu::TextureFormat,
+    ) -> Self {
+        Self::from_font_system(
+            device,
+            queue,
+            format,
+            glyphon::FontSystem::new_with_fonts(
+                [icons_font_source()].into_iter(),
+            ),
+        )
+    }
+
+    pub fn new_isolated(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let mut db = glyphon::fontdb::Database::new();
+        let _ = db.load_font_source(icons_font_source());
+
+        // A fixed locale keeps font fallback independent of the system
+        Self::from_font_system(
+            device,
+            queue,
+            format,
+            glyphon::FontSystem::new_with_locale_and_db(
+                String::from("en-US"),
+                db,
+            ),
+        )
+    }
+
+    fn from_font_system(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        font_system: glyphon::FontSystem,
     ) -> Self {
         Pipeline {
-            font_system: RefCell::new(glyphon::FontSystem::new_with_fonts(
-                [glyphon::fontdb::Source::Binary(Arc::new(
-                    include_bytes!("../fonts/Iced-Icons.ttf").as_slice(),
-                ))]
-                .into_iter(),
-            )),
+            font_system: RefCell::new(font_system),
             renderers: Vec::new(),
             atlas: glyphon::TextAtlas::with_color_mode(
                 device,
@@ -45,17 +101,135 @@ impl Pipeline {
                     glyphon::ColorMode::Web
                 },
             ),
+            swash_cache: glyphon::SwashCache::new(),
+            format,
+            decoration_pipeline: quad::Pipeline::new(device, format),
+            decorations: Vec::new(),
             prepare_layer: 0,
-            cache: RefCell::new(Cache::new()),
+            decoration_layer: 0,
+            atlas_overflows: 0,
+            on_exhaustion: None,
+            default_color: None,
+            cache: RefCell::new(Cache::new(
+                None,
+                None,
+                HashBuilder::new(None),
+                None,
+            )),
         }
     }
 
-    pub fn load_font(&mut self, bytes: Cow<'static, [u8]>) {
-        let _ = self.font_system.get_mut().db_mut().load_font_source(
-            glyphon::fontdb::Source::Binary(Arc::new(bytes.into_owned())),
-        );
+    pub fn with_settings(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        settings: &Settings,
+    ) -> Self {
+        let pipeline = if settings.text_system_fonts {
+            Self::new(device, queue, format)
+        } else {
+            Self::new_isolated(device, queue, format)
+        };
+
+        let hasher = match settings.text_cache_hasher {
+            Some(hasher) => HashBuilder::Custom(hasher),
+            None => HashBuilder::new(settings.text_cache_seed),
+        };
+
+        Pipeline {
+            cache: RefCell::new(Cache::new(
+                settings.text_cache_capacity,
+                settings.text_cache_budget,
+                hasher,
+                settings.text_missing_glyph,
+            )),
+            ..pipeline
+        }
+    }
+
+    pub fn load_font(
+        &mut self,
+        bytes: Cow<'static, [u8]>,
+    ) -> Result<FontHandle, font::Error> {
+        let has_signature = has_font_signature(&bytes);
+
+        let db = self.font_system.get_mut().db_mut();
+
+        let faces = db.load_font_source(glyphon::fontdb::Source::Binary(
+            Arc::new(bytes.into_owned()),
+        ));
+
+        if faces.is_empty() {
+            return Err(if has_signature {
+                font::Error::Parse
+            } else {
+                font::Error::NoFaces
+            });
+        }
+
+        let mut families: Vec<String> = faces
+            .iter()
+            .filter_map(|id| db.face(*id))
+            .flat_map(|face| face.families.iter())
+            .map(|(family, _language)| family.clone())
+            .collect();
+
+        families.dedup();
+
+        self.cache.get_mut().clear(self.font_system.get_mut());
+
+        Ok(FontHandle {
+            faces: faces.into_iter().collect(),
+            families,
+        })
+    }
+
+    pub fn with_font_system<T>(
+        &mut self,
+        f: impl FnOnce(&mut glyphon::FontSystem) -> T,
+    ) -> T {
+        let result = f(self.font_system.get_mut());
+
+        // The fonts may have changed, so any cached layout could be stale
+        self.cache.get_mut().clear(self.font_system.get_mut());
+
+        result
+    }
+
+    pub fn unload_font(&mut self, handle: FontHandle) {
+        let db = self.font_system.get_mut().db_mut();
+
+        for face in &handle.faces {
+            db.remove_face(*face);
+        }
+
+        self.cache
+            .get_mut()
+            .invalidate_faces(self.font_system.get_mut(), &handle.faces);
+    }
+
+    pub fn available_families(&self) -> Vec<String> {
+        let font_system = self.font_system.borrow();
+
+        let mut families: Vec<String> = font_system
+            .db()
+            .faces()
+            .flat_map(|face| face.families.iter())
+            .map(|(family, _language)| family.clone())
+            .collect();
+
+        families.sort_unstable();
+        families.dedup();
+
+        families
+    }
 
-        self.cache = RefCell::new(Cache::new());
+    pub fn has_family(&self, name: &str) -> bool {
+        self.font_system.borrow().db().faces().any(|face| {
+            face.families
+                .iter()
+                .any(|(family, _language)| family == name)
+        })
     }
 
     pub fn prepare(
@@ -64,9 +238,10 @@ impl Pipeline {
         queue: &wgpu::Queue,
         sections: &[Text<'_>],
         bounds: Rectangle,
+        transformation: Transformation,
         scale_factor: f32,
         target_size: Size<u32>,
-    ) {
+    ) -> Preparation {
         if self.renderers.len() <= self.prepare_layer {
             self.renderers.push(glyphon::TextRenderer::new(
                 &mut self.atlas,
@@ -79,31 +254,20 @@ impl Pipeline {
         let font_system = self.font_system.get_mut();
         let renderer = &mut self.renderers[self.prepare_layer];
         let cache = self.cache.get_mut();
-
-        if self.prepare_layer == 0 {
-            cache.trim(Purpose::Drawing);
-        }
+        let default_color = self.default_color;
 
         let keys: Vec<_> = sections
             .iter()
             .map(|section| {
+                if let Some(handle) = section.handle {
+                    if cache.touch(handle.0) {
+                        return handle.0;
+                    }
+                }
+
                 let (key, _) = cache.allocate(
                     font_system,
-                    Key {
-                        content: section.content,
-                        size: section.size,
-                        line_height: f32::from(
-                            section
-                                .line_height
-                                .to_absolute(Pixels(section.size)),
-                        ),
-                        font: section.font,
-                        bounds: Size {
-                            width: section.bounds.width,
-                            height: section.bounds.height,
-                        },
-                        shaping: section.shaping,
-                    },
+                    Key::from(section),
                     Purpose::Drawing,
                 );
 
@@ -111,66 +275,122 @@ impl Pipeline {
             })
             .collect();
 
+        // Once drawn, preheated text is trimmed like any other
+        for key in &keys {
+            let _ = cache.preheated.remove(key);
+        }
+
+        let mut decorations = quad::Batch::default();
+
+        for (section, key) in sections.iter().zip(keys.iter()) {
+            if section.background.is_none()
+                && section.selections.is_empty()
+                && section.decoration == primitive::Decoration::None
+            {
+                continue;
+            }
+
+            let entry = cache.get(key).expect("Get cached buffer");
+
+            // Backgrounds and selections are added first, so they are drawn
+            // below any line
+            fill_background(&mut decorations, section, entry, scale_factor);
+            select(&mut decorations, section, entry, scale_factor);
+
+            decorate(
+                &mut decorations,
+                font_system,
+                section,
+                entry,
+                resolve_color(section.color, default_color),
+                scale_factor,
+            );
+        }
+
+        // Any colored glyphs are painted over before the cache is borrowed
+        for (section, key) in sections.iter().zip(keys.iter()) {
+            for (_, _, paint) in layers(section, default_color) {
+                if let Some(paint) = paint {
+                    cache.paint(font_system, *key, paint);
+                }
+            }
+        }
+
         let bounds = bounds * scale_factor;
 
-        let text_areas =
-            sections
-                .iter()
-                .zip(keys.iter())
-                .filter_map(|(section, key)| {
-                    let entry = cache.get(key).expect("Get cached buffer");
+        // Empty sections may still have a background, but no glyphs
+        let text_areas = sections
+            .iter()
+            .zip(keys.iter())
+            .filter(|(section, _)| !section.content.is_empty())
+            .flat_map(|(section, key)| {
+                let entry = cache.get(key).expect("Get cached buffer");
 
-                    let x = section.bounds.x * scale_factor;
-                    let y = section.bounds.y * scale_factor;
+                let scale = section.scale_override.unwrap_or(scale_factor);
+                let ratio = scale / scale_factor;
 
-                    let max_width = entry.bounds.width * scale_factor;
-                    let total_height = entry.bounds.height * scale_factor;
+                let position = align(section, entry, ratio);
 
-                    let left = match section.horizontal_alignment {
-                        alignment::Horizontal::Left => x,
-                        alignment::Horizontal::Center => x - max_width / 2.0,
-                        alignment::Horizontal::Right => x - max_width,
-                    };
+                layers(section, default_color)
+                    .into_iter()
+                    .filter_map(move |(offset, color, paint)| {
+                        let mut left = (position.x + offset.x) * scale_factor;
+                        let mut top = (position.y + offset.y) * scale_factor;
 
-                    let top = match section.vertical_alignment {
-                        alignment::Vertical::Top => y,
-                        alignment::Vertical::Center => y - total_height / 2.0,
-                        alignment::Vertical::Bottom => y - total_height,
-                    };
+                        if section.pixel_snap {
+                            left = left.round();
+                            top = top.round();
+                        }
 
-                    let section_bounds = Rectangle {
-                        x: left,
-                        y: top,
-                        width: section.bounds.width * scale_factor,
-                        height: section.bounds.height * scale_factor,
-                    };
+                        let section_bounds = Rectangle {
+                            x: left,
+                            y: top,
+                            width: section.bounds.width * scale,
+                            height: section.bounds.height * scale,
+                        };
 
-                    let clip_bounds = bounds.intersection(&section_bounds)?;
+                        let clip_bounds = match section.clip_bounds {
+                            Some(clip_bounds) => bounds
+                                .intersection(&(clip_bounds * scale_factor))?
+                                .intersection(&section_bounds)?,
+                            None => bounds.intersection(&section_bounds)?,
+                        };
 
-                    Some(glyphon::TextArea {
-                        buffer: &entry.buffer,
-                        left,
-                        top,
-                        scale: scale_factor,
-                        bounds: glyphon::TextBounds {
+                        let bounds = glyphon::TextBounds {
                             left: clip_bounds.x as i32,
                             top: clip_bounds.y as i32,
                             right: (clip_bounds.x + clip_bounds.width) as i32,
                             bottom: (clip_bounds.y + clip_bounds.height) as i32,
-                        },
-                        default_color: {
-                            let [r, g, b, a] =
-                                color::pack(section.color).components();
-
-                            glyphon::Color::rgba(
-                                (r * 255.0) as u8,
-                                (g * 255.0) as u8,
-                                (b * 255.0) as u8,
-                                (a * 255.0) as u8,
-                            )
-                        },
+                        };
+
+                        let areas = entry
+                            .paragraphs
+                            .areas(&entry.buffer, top, scale, bounds)
+                            .into_iter()
+                            .flat_map(move |(top, bounds)| {
+                                let area = glyphon::TextArea {
+                                    buffer: entry.painted(paint),
+                                    left,
+                                    top,
+                                    scale,
+                                    bounds,
+                                    default_color: to_color(color),
+                                };
+
+                                std::iter::once(area).chain(substitute_areas(
+                                    entry,
+                                    left,
+                                    top,
+                                    scale,
+                                    bounds,
+                                    to_color(color),
+                                ))
+                            });
+
+                        Some(areas)
                     })
-                });
+                    .flatten()
+            });
 
         let result = renderer.prepare(
             device,
@@ -182,17 +402,46 @@ impl Pipeline {
                 height: target_size.height,
             },
             text_areas,
-            &mut glyphon::SwashCache::new(),
+            &mut self.swash_cache,
         );
 
         match result {
             Ok(()) => {
+                if self.decorations.len() <= self.prepare_layer {
+                    self.decorations.push(None);
+                }
+
+                self.decorations[self.prepare_layer] = if decorations.is_empty()
+                {
+                    None
+                } else {
+                    self.decoration_pipeline.prepare(
+                        device,
+                        queue,
+                        &decorations,
+                        transformation,
+                        scale_factor,
+                    );
+
+                    self.decoration_layer += 1;
+
+                    Some(Decorations {
+                        layer: self.decoration_layer - 1,
+                        quads: decorations,
+                    })
+                };
+
                 self.prepare_layer += 1;
+
+                Preparation::Prepared
             }
             Err(glyphon::PrepareError::AtlasFull) => {
                 // If the atlas cannot grow, then all bets are off.
                 // Instead of panicking, we will just pray that the result
                 // will be somewhat readable...
+                self.exhaust(sections.len());
+
+                Preparation::AtlasExhausted
             }
         }
     }
@@ -205,6 +454,15 @@ impl Pipeline {
     ) {
         let renderer = &self.renderers[layer];
 
+        if let Some(Some(decorations)) = self.decorations.get(layer) {
+            self.decoration_pipeline.render(
+                decorations.layer,
+                bounds,
+                &decorations.quads,
+                render_pass,
+            );
+        }
+
         render_pass.set_scissor_rect(
             bounds.x,
             bounds.y,
@@ -217,280 +475,4267 @@ impl Pipeline {
             .expect("Render text");
     }
 
-    pub fn end_frame(&mut self) {
-        self.atlas.trim();
+    pub fn render_to_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        section: &Text<'_>,
+        size: Size<u32>,
+        scale_factor: f32,
+    ) -> wgpu::Texture {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("iced_wgpu::text offscreen texture"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
 
-        self.prepare_layer = 0;
-    }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-    pub fn trim_measurements(&mut self) {
-        self.cache.get_mut().trim(Purpose::Measuring);
-    }
+        let font_system = self.font_system.get_mut();
+        let cache = self.cache.get_mut();
 
-    pub fn measure(
-        &self,
-        content: &str,
-        size: f32,
-        line_height: LineHeight,
-        font: Font,
-        bounds: Size,
-        shaping: Shaping,
-    ) -> Size {
-        let mut cache = self.cache.borrow_mut();
+        let (hash, _) =
+            cache.allocate(font_system, Key::from(section), Purpose::Drawing);
 
-        let line_height = f32::from(line_height.to_absolute(Pixels(size)));
+        let paint =
+            (section.opacity < 1.0).then_some(Paint::Opacity(section.opacity));
 
-        let (_, entry) = cache.allocate(
-            &mut self.font_system.borrow_mut(),
-            Key {
-                content,
-                size,
-                line_height,
-                font,
-                bounds,
-                shaping,
-            },
-            Purpose::Measuring,
+        if let Some(paint) = paint {
+            cache.paint(font_system, hash, paint);
+        }
+
+        let entry = cache.get(&hash).expect("Get cached buffer");
+
+        let mut renderer = glyphon::TextRenderer::new(
+            &mut self.atlas,
+            device,
+            Default::default(),
+            None,
         );
 
-        entry.bounds
-    }
+        let color = resolve_color(section.color, self.default_color);
 
-    pub fn hit_test(
-        &self,
-        content: &str,
-        size: f32,
-        line_height: LineHeight,
-        font: Font,
-        bounds: Size,
-        shaping: Shaping,
-        point: Point,
-        _nearest_only: bool,
-    ) -> Option<Hit> {
-        let mut cache = self.cache.borrow_mut();
+        let bounds = glyphon::TextBounds {
+            left: 0,
+            top: 0,
+            right: size.width as i32,
+            bottom: size.height as i32,
+        };
 
-        let line_height = f32::from(line_height.to_absolute(Pixels(size)));
+        let default_color = to_color(Color {
+            a: color.a * section.opacity,
+            ..color
+        });
 
-        let (_, entry) = cache.allocate(
-            &mut self.font_system.borrow_mut(),
-            Key {
-                content,
-                size,
-                line_height,
-                font,
-                bounds,
-                shaping,
+        // The text is drawn at the origin of the texture, ignoring the
+        // position of its bounds
+        let result = renderer.prepare(
+            device,
+            queue,
+            font_system,
+            &mut self.atlas,
+            glyphon::Resolution {
+                width: size.width,
+                height: size.height,
             },
-            Purpose::Measuring,
+            entry
+                .paragraphs
+                .areas(&entry.buffer, 0.0, scale_factor, bounds)
+                .into_iter()
+                .flat_map(|(top, bounds)| {
+                    std::iter::once(glyphon::TextArea {
+                        buffer: entry.painted(paint),
+                        left: 0.0,
+                        top,
+                        scale: scale_factor,
+                        bounds,
+                        default_color,
+                    })
+                    .chain(substitute_areas(
+                        entry,
+                        0.0,
+                        top,
+                        scale_factor,
+                        bounds,
+                        default_color,
+                    ))
+                }),
+            &mut self.swash_cache,
         );
 
-        let cursor = entry.buffer.hit(point.x, point.y)?;
+        if let Err(glyphon::PrepareError::AtlasFull) = result {
+            self.exhaust(1);
 
-        Some(Hit::CharOffset(cursor.index))
-    }
-}
+            return texture;
+        }
 
-fn measure(buffer: &glyphon::Buffer) -> Size {
-    let (width, total_lines) = buffer
-        .layout_runs()
-        .fold((0.0, 0usize), |(width, total_lines), run| {
-            (run.line_w.max(width), total_lines + 1)
-        });
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("iced_wgpu::text offscreen render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
 
-    Size::new(width, total_lines as f32 * buffer.metrics().line_height)
-}
+        renderer
+            .render(&self.atlas, &mut render_pass)
+            .expect("Render text");
 
-fn to_family(family: font::Family) -> glyphon::Family<'static> {
-    match family {
-        font::Family::Name(name) => glyphon::Family::Name(name),
-        font::Family::SansSerif => glyphon::Family::SansSerif,
-        font::Family::Serif => glyphon::Family::Serif,
-        font::Family::Cursive => glyphon::Family::Cursive,
-        font::Family::Fantasy => glyphon::Family::Fantasy,
-        font::Family::Monospace => glyphon::Family::Monospace,
+        drop(render_pass);
+
+        texture
     }
-}
 
-fn to_weight(weight: font::Weight) -> glyphon::Weight {
-    match weight {
-        font::Weight::Thin => glyphon::Weight::THIN,
-        font::Weight::ExtraLight => glyphon::Weight::EXTRA_LIGHT,
-        font::Weight::Light => glyphon::Weight::LIGHT,
-        font::Weight::Normal => glyphon::Weight::NORMAL,
-        font::Weight::Medium => glyphon::Weight::MEDIUM,
-        font::Weight::Semibold => glyphon::Weight::SEMIBOLD,
-        font::Weight::Bold => glyphon::Weight::BOLD,
-        font::Weight::ExtraBold => glyphon::Weight::EXTRA_BOLD,
-        font::Weight::Black => glyphon::Weight::BLACK,
+    pub fn end_frame(&mut self) {
+        self.trim_atlas();
+        self.trim_render_cache();
+        self.reset_layers();
     }
-}
 
-fn to_stretch(stretch: font::Stretch) -> glyphon::Stretch {
-    match stretch {
-        font::Stretch::UltraCondensed => glyphon::Stretch::UltraCondensed,
-        font::Stretch::ExtraCondensed => glyphon::Stretch::ExtraCondensed,
-        font::Stretch::Condensed => glyphon::Stretch::Condensed,
-        font::Stretch::SemiCondensed => glyphon::Stretch::SemiCondensed,
-        font::Stretch::Normal => glyphon::Stretch::Normal,
-        font::Stretch::SemiExpanded => glyphon::Stretch::SemiExpanded,
-        font::Stretch::Expanded => glyphon::Stretch::Expanded,
-        font::Stretch::ExtraExpanded => glyphon::Stretch::ExtraExpanded,
-        font::Stretch::UltraExpanded => glyphon::Stretch::UltraExpanded,
+    pub fn trim_atlas(&mut self) {
+        self.atlas.trim();
     }
-}
 
-fn to_shaping(shaping: Shaping) -> glyphon::Shaping {
-    match shaping {
-        Shaping::Basic => glyphon::Shaping::Basic,
-        Shaping::Advanced => glyphon::Shaping::Advanced,
+    pub fn trim_render_cache(&mut self) {
+        self.cache.get_mut().trim(Purpose::Drawing);
     }
-}
 
-struct Cache {
-    entries: FxHashMap<KeyHash, Entry>,
-    aliases: FxHashMap<KeyHash, KeyHash>,
-    recently_measured: FxHashSet<KeyHash>,
-    recently_drawn: FxHashSet<KeyHash>,
-    hasher: HashBuilder,
-}
+    pub fn reset_layers(&mut self) {
+        self.decoration_pipeline.end_frame();
 
-struct Entry {
-    buffer: glyphon::Buffer,
-    bounds: Size,
-}
+        self.prepare_layer = 0;
+        self.decoration_layer = 0;
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Purpose {
-    Measuring,
-    Drawing,
-}
+    pub fn preheat(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        sections: &[Text<'_>],
+        scale_factor: f32,
+    ) {
+        let font_system = self.font_system.get_mut();
+        let cache = self.cache.get_mut();
 
-#[cfg(not(target_arch = "wasm32"))]
-type HashBuilder = twox_hash::RandomXxHashBuilder64;
+        let keys: Vec<_> = sections
+            .iter()
+            .map(|section| {
+                let (key, _) = cache.allocate(
+                    font_system,
+                    Key::from(section),
+                    Purpose::Drawing,
+                );
 
-#[cfg(target_arch = "wasm32")]
-type HashBuilder = std::hash::BuildHasherDefault<twox_hash::XxHash64>;
+                let _ = cache.preheated.insert(key);
 
-impl Cache {
-    fn new() -> Self {
-        Self {
-            entries: FxHashMap::default(),
-            aliases: FxHashMap::default(),
-            recently_measured: FxHashSet::default(),
-            recently_drawn: FxHashSet::default(),
-            hasher: HashBuilder::default(),
+                key
+            })
+            .collect();
+
+        let text_areas =
+            sections.iter().zip(keys.iter()).flat_map(|(section, key)| {
+                let entry = cache.get(key).expect("Get cached buffer");
+                let scale = section.scale_override.unwrap_or(scale_factor);
+                let bounds = glyphon::TextBounds::default();
+                let color = to_color(Color::BLACK);
+
+                std::iter::once(glyphon::TextArea {
+                    buffer: &entry.buffer,
+                    left: 0.0,
+                    top: 0.0,
+                    scale,
+                    bounds,
+                    default_color: color,
+                })
+                .chain(substitute_areas(entry, 0.0, 0.0, scale, bounds, color))
+            });
+
+        let result = upload(
+            device,
+            queue,
+            font_system,
+            &mut self.atlas,
+            &mut self.swash_cache,
+            text_areas,
+        );
+
+        if let Err(glyphon::PrepareError::AtlasFull) = result {
+            self.exhaust(sections.len());
         }
     }
 
-    fn get(&self, key: &KeyHash) -> Option<&Entry> {
-        self.entries.get(key)
+    pub fn warm_glyphs(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chars: &str,
+        size: f32,
+        font: Font,
+        scale_factor: f32,
+    ) {
+        let font_system = self.font_system.get_mut();
+        let cache = self.cache.get_mut();
+
+        let (_, entry) = cache.allocate(
+            font_system,
+            Key::from(&MeasureRequest {
+                content: chars,
+                size,
+                line_height: LineHeight::default(),
+                font,
+                bounds: Size::INFINITY,
+                shaping: Shaping::Advanced,
+            }),
+            Purpose::Measuring,
+        );
+
+        let result = upload(
+            device,
+            queue,
+            font_system,
+            &mut self.atlas,
+            &mut self.swash_cache,
+            [glyphon::TextArea {
+                buffer: &entry.buffer,
+                left: 0.0,
+                top: 0.0,
+                scale: scale_factor,
+                bounds: glyphon::TextBounds::default(),
+                default_color: to_color(Color::BLACK),
+            }],
+        );
+
+        if let Err(glyphon::PrepareError::AtlasFull) = result {
+            self.exhaust(1);
+        }
     }
 
-    fn allocate(
+    pub fn clear_cache(&mut self) {
+        self.cache.get_mut().clear(self.font_system.get_mut());
+    }
+
+    pub fn pin(&mut self, section: &Text<'_>) -> Handle {
+        self.cache
+            .get_mut()
+            .pin(self.font_system.get_mut(), Key::from(section))
+    }
+
+    pub fn append(
         &mut self,
-        font_system: &mut glyphon::FontSystem,
-        key: Key<'_>,
-        purpose: Purpose,
-    ) -> (KeyHash, &mut Entry) {
-        let hash = key.hash(self.hasher.build_hasher());
+        handle: Handle,
+        content: &str,
+        font: Font,
+    ) -> bool {
+        self.cache.get_mut().append(
+            self.font_system.get_mut(),
+            handle,
+            content,
+            font,
+        )
+    }
 
-        let recently_used = match purpose {
-            Purpose::Measuring => &mut self.recently_measured,
-            Purpose::Drawing => &mut self.recently_drawn,
-        };
+    pub fn unpin(&mut self, handle: Handle) {
+        let _ = self.cache.get_mut().pinned.remove(&handle.0);
+    }
 
-        if let Some(hash) = self.aliases.get(&hash) {
-            let _ = recently_used.insert(*hash);
+    pub fn invalidate(&mut self, section: &Text<'_>) -> bool {
+        self.cache.get_mut().invalidate(Key::from(section))
+    }
+
+    pub fn trim_to_budget(&mut self, max_bytes: usize) {
+        self.cache.get_mut().evict_to(None, Some(max_bytes));
+    }
+
+    pub fn trim_measurements(&mut self) {
+        self.cache.get_mut().trim(Purpose::Measuring);
+    }
+
+    pub fn set_default_color(&mut self, color: Option<Color>) {
+        self.default_color = color;
+    }
+
+    pub fn atlas_overflows(&self) -> u64 {
+        self.atlas_overflows
+    }
+
+    pub fn on_exhaustion(
+        &mut self,
+        callback: Option<Box<dyn FnMut(Exhaustion) + Send>>,
+    ) {
+        self.on_exhaustion = callback;
+    }
+
+    fn exhaust(&mut self, sections: usize) {
+        self.atlas_overflows += 1;
 
-            return (*hash, self.entries.get_mut(hash).unwrap());
+        log::warn!("The text atlas is full and cannot grow any further");
+
+        if let Some(callback) = &mut self.on_exhaustion {
+            callback(Exhaustion {
+                sections,
+                overflows: self.atlas_overflows,
+            });
         }
+    }
 
-        if let hash_map::Entry::Vacant(entry) = self.entries.entry(hash) {
-            let metrics = glyphon::Metrics::new(key.size, key.line_height);
-            let mut buffer = glyphon::Buffer::new(font_system, metrics);
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
+    }
 
-            buffer.set_size(
-                font_system,
-                key.bounds.width,
-                key.bounds.height.max(key.line_height),
-            );
-            buffer.set_text(
-                font_system,
-                key.content,
-                glyphon::Attrs::new()
-                    .family(to_family(key.font.family))
-                    .weight(to_weight(key.font.weight))
-                    .stretch(to_stretch(key.font.stretch)),
-                to_shaping(key.shaping),
-            );
+    pub fn measure(
+        &self,
+        content: &str,
+        size: f32,
+        line_height: LineHeight,
+        font: Font,
+        bounds: Size,
+        shaping: Shaping,
+    ) -> Size {
+        let key = Key::from(&MeasureRequest {
+            content,
+            size,
+            line_height,
+            font,
+            bounds,
+            shaping,
+        });
 
-            let bounds = measure(&buffer);
-            let _ = entry.insert(Entry { buffer, bounds });
+        if let Some(bounds) = empty_bounds(key) {
+            return bounds;
+        }
 
-            for bounds in [
-                bounds,
-                Size {
-                    width: key.bounds.width,
-                    ..bounds
-                },
-            ] {
-                if key.bounds != bounds {
-                    let _ = self.aliases.insert(
-                        Key { bounds, ..key }.hash(self.hasher.build_hasher()),
-                        hash,
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            key,
+            Purpose::Measuring,
+        );
+
+        entry.bounds
+    }
+
+    pub fn measure_batch(&self, requests: &[MeasureRequest<'_>]) -> Vec<Size> {
+        let mut cache = self.cache.borrow_mut();
+        let font_system = &mut self.font_system.borrow_mut();
+
+        requests
+            .iter()
+            .map(|request| {
+                let key = Key::from(request);
+
+                if let Some(bounds) = empty_bounds(key) {
+                    return bounds;
+                }
+
+                let (_, entry) =
+                    cache.allocate(font_system, key, Purpose::Measuring);
+
+                entry.bounds
+            })
+            .collect()
+    }
+
+    pub fn measure_text(&self, text: &Text<'_>) -> Size {
+        let key = Key::from(text);
+
+        if let Some(bounds) = empty_bounds(key) {
+            return bounds;
+        }
+
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            key,
+            Purpose::Measuring,
+        );
+
+        entry.bounds
+    }
+
+    pub fn complexity(&self, request: &MeasureRequest<'_>) -> Complexity {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(request),
+            Purpose::Measuring,
+        );
+
+        entry.buffer.layout_runs().fold(
+            Complexity::default(),
+            |complexity, run| Complexity {
+                glyphs: complexity.glyphs + run.glyphs.len(),
+                lines: complexity.lines + 1,
+            },
+        )
+    }
+
+    pub fn missing_glyphs(&self, content: &str, font: Font) -> Vec<char> {
+        let mut cache = self.cache.borrow_mut();
+
+        // Only advanced shaping falls back to other fonts
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(&MeasureRequest {
+                content,
+                size: 16.0,
+                line_height: LineHeight::default(),
+                font,
+                bounds: Size::INFINITY,
+                shaping: Shaping::Advanced,
+            }),
+            Purpose::Measuring,
+        );
+
+        let mut missing = Vec::new();
+
+        for run in entry.buffer.layout_runs() {
+            for glyph in run.glyphs.iter().filter(|glyph| glyph.glyph_id == 0) {
+                for c in run.text[glyph.start..glyph.end].chars() {
+                    if !missing.contains(&c) {
+                        missing.push(c);
+                    }
+                }
+            }
+        }
+
+        missing
+    }
+
+    pub fn cell_size(&self, size: f32, font: Font) -> Size {
+        let mut cache = self.cache.borrow_mut();
+
+        if let Some(cell) = cache.cells.get(&(font, size.to_bits())) {
+            return *cell;
+        }
+
+        let font_system = &mut self.font_system.borrow_mut();
+        let line_height = LineHeight::default();
+
+        let (_, entry) = cache.allocate(
+            font_system,
+            Key::from(&MeasureRequest {
+                content: "M",
+                size,
+                line_height,
+                font,
+                bounds: Size::INFINITY,
+                shaping: Shaping::Basic,
+            }),
+            Purpose::Measuring,
+        );
+
+        let glyph = entry
+            .buffer
+            .layout_runs()
+            .next()
+            .and_then(|run| run.glyphs.first())
+            .map(|glyph| (glyph.font_id, glyph.w));
+
+        let width = match glyph {
+            Some((id, width)) => {
+                let is_monospaced = font_system
+                    .db()
+                    .face(id)
+                    .map_or(false, |face| face.monospaced);
+
+                if !is_monospaced {
+                    log::warn!(
+                        "{font:?} is not monospaced, so its cells may not \
+                        be uniform"
                     );
                 }
+
+                width
             }
+            None => size,
+        };
+
+        let cell =
+            Size::new(width, f32::from(line_height.to_absolute(Pixels(size))));
+
+        let _ = cache.cells.insert((font, size.to_bits()), cell);
+
+        cell
+    }
+
+    pub fn measure_columns(
+        &self,
+        content: &str,
+        size: f32,
+        font: Font,
+        columns: usize,
+        shaping: Shaping,
+    ) -> Size {
+        let cell = self.cell_size(size, font);
+
+        // Half a cell of slack keeps rounding errors in the advances from
+        // wrapping the last column early
+        let key = Key {
+            bounds: Size::new(
+                cell.width * (columns as f32 + 0.5),
+                f32::INFINITY,
+            ),
+            wrap: primitive::Wrap::Glyph,
+            ..Key::from(&MeasureRequest {
+                content,
+                size,
+                line_height: LineHeight::default(),
+                font,
+                bounds: Size::INFINITY,
+                shaping,
+            })
+        };
+
+        if let Some(bounds) = empty_bounds(key) {
+            return bounds;
         }
 
-        let _ = recently_used.insert(hash);
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            key,
+            Purpose::Measuring,
+        );
+
+        entry.bounds
+    }
+
+    pub fn line_widths(&self, request: &MeasureRequest<'_>) -> Vec<f32> {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(request),
+            Purpose::Measuring,
+        );
+
+        entry.buffer.layout_runs().map(|run| run.line_w).collect()
+    }
+
+    pub fn first_baseline(&self, request: &MeasureRequest<'_>) -> f32 {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(request),
+            Purpose::Measuring,
+        );
+
+        entry
+            .buffer
+            .layout_runs()
+            .next()
+            .map_or(0.0, |run| run.line_y)
+    }
+
+    pub fn line_breaks(&self, request: &MeasureRequest<'_>) -> Vec<usize> {
+        let mut cache = self.cache.borrow_mut();
+
+        let key = Key::from(request);
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key {
+                bounds: Size::new(key.bounds.width, f32::INFINITY),
+                ..key
+            },
+            Purpose::Measuring,
+        );
+
+        let line_starts = line_starts(request.content);
+
+        let mut previous_line = None;
+
+        entry
+            .buffer
+            .layout_runs()
+            .filter_map(|run| {
+                let is_wrapped = previous_line == Some(run.line_i);
+                previous_line = Some(run.line_i);
+
+                if !is_wrapped {
+                    return None;
+                }
+
+                let start = run.glyphs.iter().map(|glyph| glyph.start).min()?;
+
+                Some(line_starts.get(run.line_i)? + start)
+            })
+            .collect()
+    }
+
+    pub fn measure_overflow(&self, text: &Text<'_>) -> Overflow {
+        let mut cache = self.cache.borrow_mut();
+
+        let key = sanitize(Key::from(text));
+
+        // Lay out every line, even the ones that do not fit the bounds
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key {
+                bounds: Size::new(key.bounds.width, f32::INFINITY),
+                ..key
+            },
+            Purpose::Measuring,
+        );
+
+        // Paragraphs are moved down by the spacing before them, which may
+        // push their lines out of the bounds
+        let clipped_lines = entry
+            .buffer
+            .layout_runs()
+            .enumerate()
+            .filter(|(i, run)| {
+                let height =
+                    key.bounds.height - entry.paragraphs.offset(run.line_i);
+
+                *i >= visible_lines(height, key.line_height)
+            })
+            .count();
+
+        Overflow {
+            bounds: entry.bounds,
+            overflows: clipped_lines > 0,
+            clipped_lines,
+        }
+    }
+
+    pub fn fits(&self, request: &MeasureRequest<'_>) -> bool {
+        let mut cache = self.cache.borrow_mut();
+
+        let key = sanitize(Key::from(request));
+        let max_lines = visible_lines(key.bounds.height, key.line_height);
+
+        // A single line past the bounds is enough to notice an overflow, so
+        // the rest of the lines are never laid out
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key {
+                bounds: Size::new(
+                    key.bounds.width,
+                    (max_lines + 1) as f32 * key.line_height,
+                ),
+                ..key
+            },
+            Purpose::Measuring,
+        );
+
+        let mut runs = entry.buffer.layout_runs();
+
+        runs.by_ref()
+            .take(max_lines)
+            .all(|run| run.line_w <= key.bounds.width)
+            && runs.next().is_none()
+    }
+
+    pub fn fit_size(
+        &self,
+        request: &MeasureRequest<'_>,
+        wrap: primitive::Wrap,
+        min_size: f32,
+    ) -> f32 {
+        const PRECISION: f32 = 0.1;
+
+        let bounds = request.bounds;
+        let max_size = request.size;
+
+        // The result never exceeds the size of the request
+        let min_size = min_size.min(max_size);
+
+        let mut cache = self.cache.borrow_mut();
+        let font_system = &mut self.font_system.borrow_mut();
+
+        let mut fits = |size: f32| {
+            let key = Key {
+                bounds: Size::new(bounds.width, f32::INFINITY),
+                wrap,
+                ..Key::from(&MeasureRequest { size, ..*request })
+            };
+
+            let (_, entry) =
+                cache.allocate(font_system, key, Purpose::Measuring);
+
+            entry.bounds.width <= bounds.width
+                && entry.bounds.height <= bounds.height
+        };
+
+        if fits(max_size) {
+            return max_size;
+        }
+
+        let (mut low, mut high) = (min_size, max_size);
+
+        while high - low > PRECISION {
+            let size = (low + high) / 2.0;
+
+            if fits(size) {
+                low = size;
+            } else {
+                high = size;
+            }
+        }
+
+        low
+    }
+
+    pub fn measure_detailed(&self, text: &Text<'_>) -> Metrics {
+        let mut cache = self.cache.borrow_mut();
+        let font_system = &mut self.font_system.borrow_mut();
+
+        let (_, entry) =
+            cache.allocate(font_system, Key::from(text), Purpose::Measuring);
+
+        let first_run = entry.buffer.layout_runs().next();
+
+        let font = first_run
+            .as_ref()
+            .and_then(|run| run.glyphs.first())
+            .map(|glyph| glyph.font_id)
+            .or_else(|| {
+                let attrs = to_attrs(text.font);
+
+                font_system.db().query(&glyphon::fontdb::Query {
+                    families: &[attrs.family],
+                    weight: attrs.weight,
+                    stretch: attrs.stretch,
+                    style: attrs.style,
+                })
+            })
+            .and_then(|id| font_system.get_font(id));
+
+        let (ascent, descent, line_gap) = font
+            .map(|font| {
+                let metrics = font.as_swash().metrics(&[]);
+                let scale = text.size / f32::from(metrics.units_per_em);
+
+                (
+                    metrics.ascent * scale,
+                    metrics.descent.abs() * scale,
+                    metrics.leading * scale,
+                )
+            })
+            .unwrap_or((text.size * 0.8, text.size * 0.2, 0.0));
+
+        let baseline = first_run.map(|run| run.line_y).unwrap_or(ascent);
+
+        Metrics {
+            bounds: entry.bounds,
+            ascent,
+            descent,
+            line_gap,
+            baseline,
+        }
+    }
+
+    pub fn glyph_rects(&self, text: &Text<'_>) -> Vec<Rectangle> {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(text),
+            Purpose::Measuring,
+        );
+
+        let line_height = entry.buffer.metrics().line_height;
+
+        entry
+            .buffer
+            .layout_runs()
+            .enumerate()
+            .flat_map(|(i, run)| {
+                let top = i as f32 * line_height
+                    + entry.paragraphs.offset(run.line_i);
+
+                run.glyphs.iter().map(move |glyph| Rectangle {
+                    x: glyph.x,
+                    y: top,
+                    width: glyph.w,
+                    height: line_height,
+                })
+            })
+            .collect()
+    }
+
+    pub fn hit_test(
+        &self,
+        content: &str,
+        size: f32,
+        line_height: LineHeight,
+        font: Font,
+        bounds: Size,
+        shaping: Shaping,
+        point: Point,
+        nearest_only: bool,
+    ) -> Option<Hit> {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(&MeasureRequest {
+                content,
+                size,
+                line_height,
+                font,
+                bounds,
+                shaping,
+            }),
+            Purpose::Measuring,
+        );
+
+        hit(&entry.buffer, &entry.paragraphs, point, nearest_only)
+            .map(|details| Hit::CharOffset(details.index))
+    }
+
+    pub fn hit_test_text(
+        &self,
+        text: &Text<'_>,
+        point: Point,
+        nearest_only: bool,
+    ) -> Option<Hit> {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(text),
+            Purpose::Measuring,
+        );
+
+        hit(&entry.buffer, &entry.paragraphs, point, nearest_only)
+            .map(|details| entry.collapse(text.content, details))
+            .map(|details| Hit::CharOffset(details.index))
+    }
+
+    pub fn caret_rect(
+        &self,
+        request: &MeasureRequest<'_>,
+        index: usize,
+    ) -> Option<Rectangle> {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(request),
+            Purpose::Measuring,
+        );
+
+        caret(&entry.buffer, &entry.paragraphs, request.content, index)
+    }
+
+    pub fn width_to_index(
+        &self,
+        content: &str,
+        size: f32,
+        font: Font,
+        shaping: Shaping,
+        index: usize,
+    ) -> f32 {
+        let index = index.min(content.len());
+
+        // Only the line that contains the index needs to be laid out
+        let line_start = line_starts(content)
+            .into_iter()
+            .take_while(|start| *start <= index)
+            .last()
+            .unwrap_or(0);
+
+        let line = content[line_start..].split('\n').next().unwrap_or_default();
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let index = grapheme_start(line, (index - line_start).min(line.len()));
+
+        if index == 0 {
+            return 0.0;
+        }
+
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(&MeasureRequest {
+                content: line,
+                size,
+                line_height: LineHeight::default(),
+                font,
+                bounds: Size::INFINITY,
+                shaping,
+            }),
+            Purpose::Measuring,
+        );
+
+        entry
+            .buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs.iter())
+            .filter(|glyph| {
+                // A ligature counts once the index is closer to its end
+                glyph.end <= index
+                    || (glyph.start < index
+                        && index - glyph.start >= glyph.end - index)
+            })
+            .map(|glyph| glyph.w)
+            .sum()
+    }
+
+    pub fn bounds_of(
+        &self,
+        section: &Text<'_>,
+        scale_factor: f32,
+    ) -> Rectangle {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(section),
+            Purpose::Measuring,
+        );
+
+        let ratio = section
+            .scale_override
+            .map_or(1.0, |scale| scale / scale_factor);
+
+        let size =
+            Size::new(entry.bounds.width * ratio, entry.bounds.height * ratio);
+
+        let position = align(section, entry, ratio);
+
+        Rectangle::new(
+            Point::new(
+                position.x + ink_offset(section, entry) * ratio,
+                position.y,
+            ),
+            size,
+        )
+    }
+
+    pub fn text_caret(
+        &self,
+        section: &Text<'_>,
+        index: usize,
+        scale_factor: f32,
+    ) -> Option<Rectangle> {
+        let mut cache = self.cache.borrow_mut();
+
+        // The buffer is shared with drawing, so this never shapes any text
+        // that is already on screen
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(section),
+            Purpose::Measuring,
+        );
+
+        let ratio = section
+            .scale_override
+            .map_or(1.0, |scale| scale / scale_factor);
+
+        let position = align(section, entry, ratio);
+
+        caret(
+            &entry.buffer,
+            &entry.paragraphs,
+            entry.content(section.content),
+            entry.expand(index),
+        )
+        .map(|caret| Rectangle {
+            x: position.x + caret.x * ratio,
+            y: position.y + caret.y * ratio,
+            width: caret.width * ratio,
+            height: caret.height * ratio,
+        })
+    }
+
+    pub fn hit_test_detailed(
+        &self,
+        text: &Text<'_>,
+        point: Point,
+        nearest_only: bool,
+    ) -> Option<HitDetails> {
+        let mut cache = self.cache.borrow_mut();
+
+        let (_, entry) = cache.allocate(
+            &mut self.font_system.borrow_mut(),
+            Key::from(text),
+            Purpose::Measuring,
+        );
+
+        hit(&entry.buffer, &entry.paragraphs, point, nearest_only)
+            .map(|details| entry.collapse(text.content, details))
+    }
+}
+
+fn upload<'a>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    font_system: &mut glyphon::FontSystem,
+    atlas: &mut glyphon::TextAtlas,
+    swash_cache: &mut glyphon::SwashCache,
+    text_areas: impl IntoIterator<Item = glyphon::TextArea<'a>>,
+) -> Result<(), glyphon::PrepareError> {
+    // Preparing a scratch renderer uploads the glyphs to the atlas,
+    // growing it ahead of time if needed
+    let mut renderer =
+        glyphon::TextRenderer::new(atlas, device, Default::default(), None);
+
+    renderer.prepare(
+        device,
+        queue,
+        font_system,
+        atlas,
+        glyphon::Resolution {
+            width: 1,
+            height: 1,
+        },
+        text_areas,
+        swash_cache,
+    )
+}
+
+fn icons_font_source() -> glyphon::fontdb::Source {
+    glyphon::fontdb::Source::Binary(Arc::new(
+        include_bytes!("../fonts/Iced-Icons.ttf").as_slice(),
+    ))
+}
+
+fn measure(buffer: &glyphon::Buffer) -> Size {
+    let (width, total_lines) = buffer
+        .layout_runs()
+        .fold((0.0, 0usize), |(width, total_lines), run| {
+            (run.line_w.max(width), total_lines + 1)
+        });
+
+    Size::new(width, total_lines as f32 * buffer.metrics().line_height)
+}
+
+fn hit(
+    buffer: &glyphon::Buffer,
+    paragraphs: &Paragraphs,
+    point: Point,
+    nearest_only: bool,
+) -> Option<HitDetails> {
+    if !point.x.is_finite() || !point.y.is_finite() {
+        return None;
+    }
+
+    let line_height = buffer.metrics().line_height;
+
+    // Find the closest line first, and then the closest glyph within it
+    let (line, run) =
+        buffer
+            .layout_runs()
+            .enumerate()
+            .min_by(|(a, run_a), (b, run_b)| {
+                let vertical_distance = |i: &usize, line: usize| {
+                    let top = *i as f32 * line_height + paragraphs.offset(line);
+
+                    distance(point.y, top, line_height)
+                };
+
+                vertical_distance(a, run_a.line_i)
+                    .total_cmp(&vertical_distance(b, run_b.line_i))
+            })?;
+
+    // The buffer is laid out without the spacing between paragraphs, so a
+    // point in between them is not on any line
+    let cursor = buffer
+        .hit(point.x, point.y - paragraphs.offset(run.line_i))
+        .filter(|cursor| {
+            paragraphs.indices.is_empty() || cursor.line == run.line_i
+        });
+
+    if cursor.is_none() && !nearest_only {
+        return None;
+    }
+
+    let horizontal_distance =
+        |glyph: &&glyphon::LayoutGlyph| distance(point.x, glyph.x, glyph.w);
+
+    let index = cursor.map(|cursor| cursor.index).unwrap_or_else(|| {
+        run.glyphs
+            .iter()
+            .min_by(|a, b| {
+                horizontal_distance(a).total_cmp(&horizontal_distance(b))
+            })
+            .map(|glyph| {
+                // The logical start of right-to-left glyphs is on the right
+                let left_half = point.x < glyph.x + glyph.w / 2.0;
+
+                if left_half != glyph.level.is_rtl() {
+                    glyph.start
+                } else {
+                    glyph.end
+                }
+            })
+            .unwrap_or(0)
+    });
+
+    // A hit inside of a grapheme cluster, like an emoji sequence, selects it
+    // as a whole
+    let index = grapheme_start(run.text, index);
+
+    // The logical edges of a glyph, which are swapped for right-to-left text
+    let edges = |glyph: &glyphon::LayoutGlyph| {
+        if glyph.level.is_rtl() {
+            (glyph.x + glyph.w, glyph.x)
+        } else {
+            (glyph.x, glyph.x + glyph.w)
+        }
+    };
+
+    let x = run
+        .glyphs
+        .iter()
+        .find(|glyph| glyph.start == index)
+        .map(|glyph| edges(glyph).0)
+        .or_else(|| {
+            run.glyphs
+                .iter()
+                .find(|glyph| glyph.end == index)
+                .map(|glyph| edges(glyph).1)
+        })
+        .unwrap_or(0.0);
+
+    Some(HitDetails { index, line, x })
+}
+
+fn caret(
+    buffer: &glyphon::Buffer,
+    paragraphs: &Paragraphs,
+    content: &str,
+    index: usize,
+) -> Option<Rectangle> {
+    if index > content.len() {
+        return None;
+    }
+
+    let line_starts = line_starts(content);
+    let line = line_starts
+        .iter()
+        .rposition(|start| *start <= index)
+        .unwrap_or(0);
+
+    let line_height = buffer.metrics().line_height;
+
+    // The empty line after a trailing line break may not be laid out, so
+    // the end of the text is right below the last visual line
+    if line >= buffer.lines.len() && content.ends_with('\n') {
+        return Some(Rectangle {
+            x: 0.0,
+            y: buffer.layout_runs().count() as f32 * line_height
+                + paragraphs.height(buffer),
+            width: 0.0,
+            height: line_height,
+        });
+    }
+
+    let line = line.min(buffer.lines.len().saturating_sub(1));
+    let offset = index - line_starts[line];
+
+    let runs: Vec<_> = buffer
+        .layout_runs()
+        .enumerate()
+        .filter(|(_, run)| run.line_i == line)
+        .collect();
+
+    // The leading edge of a glyph comes first, so a wrapped line places the
+    // caret at the start of the next visual line
+    let leading = runs.iter().find_map(|(i, run)| {
+        run.glyphs
+            .iter()
+            .find(|glyph| glyph.start <= offset && offset < glyph.end)
+            .map(|glyph| {
+                let x = if glyph.level.is_rtl() {
+                    glyph.x + glyph.w
+                } else {
+                    glyph.x
+                };
+
+                (*i, x)
+            })
+    });
+
+    let trailing = || {
+        runs.iter().rev().find_map(|(i, run)| {
+            run.glyphs
+                .iter()
+                .find(|glyph| glyph.end == offset)
+                .map(|glyph| {
+                    let x = if glyph.level.is_rtl() {
+                        glyph.x
+                    } else {
+                        glyph.x + glyph.w
+                    };
+
+                    (*i, x)
+                })
+        })
+    };
+
+    // Empty lines have a single run without glyphs
+    let empty = || runs.first().map(|(i, _)| (*i, 0.0));
+
+    let (visual_line, x) = leading.or_else(trailing).or_else(empty)?;
+
+    Some(Rectangle {
+        x,
+        y: visual_line as f32 * line_height + paragraphs.offset(line),
+        width: 0.0,
+        height: line_height,
+    })
+}
+
+fn grapheme_start(content: &str, index: usize) -> usize {
+    if index >= content.len() {
+        return index;
+    }
+
+    content
+        .grapheme_indices(true)
+        .map(|(start, _)| start)
+        .take_while(|start| *start <= index)
+        .last()
+        .unwrap_or(0)
+}
+
+/// Returns the byte index of the grapheme cluster that follows the one at
+/// the given index of the content.
+///
+/// A cursor moved with this function never ends up in the middle of a
+/// grapheme cluster, like a sequence of emoji joined together.
+pub fn next_grapheme(content: &str, index: usize) -> usize {
+    content
+        .grapheme_indices(true)
+        .map(|(start, _)| start)
+        .find(|start| *start > index)
+        .unwrap_or(content.len())
+}
+
+/// Returns the byte index of the grapheme cluster that precedes the one at
+/// the given index of the content.
+///
+/// Like [`next_grapheme`], the result is always a grapheme boundary.
+pub fn previous_grapheme(content: &str, index: usize) -> usize {
+    content
+        .grapheme_indices(true)
+        .map(|(start, _)| start)
+        .take_while(|start| *start < index)
+        .last()
+        .unwrap_or(0)
+}
+
+fn distance(value: f32, start: f32, length: f32) -> f32 {
+    if value < start {
+        start - value
+    } else {
+        (value - start - length).max(0.0)
+    }
+}
+
+fn align(section: &Text<'_>, entry: &Entry, ratio: f32) -> Point {
+    let bounds =
+        Size::new(entry.bounds.width * ratio, entry.bounds.height * ratio);
+
+    let is_rtl = is_bounded_rtl(section, entry);
+
+    // Right-to-left paragraphs start on the right edge of their bounds, so
+    // left and right alignments swap
+    let x = match (section.horizontal_alignment, is_rtl) {
+        (
+            alignment::Horizontal::Left | alignment::Horizontal::Justify,
+            false,
+        ) => section.bounds.x,
+        (
+            alignment::Horizontal::Left | alignment::Horizontal::Justify,
+            true,
+        ) => section.bounds.x + section.bounds.width - bounds.width,
+        (alignment::Horizontal::Center, _) => {
+            section.bounds.x - bounds.width / 2.0
+        }
+        (alignment::Horizontal::Right, false) => {
+            section.bounds.x - bounds.width
+        }
+        (alignment::Horizontal::Right, true) => {
+            section.bounds.x - section.bounds.width
+        }
+    };
+
+    let x = x - ink_offset(section, entry) * ratio;
+
+    let y = match section.vertical_alignment {
+        alignment::Vertical::Top => section.bounds.y,
+        alignment::Vertical::Center => section.bounds.y - bounds.height / 2.0,
+        alignment::Vertical::Bottom => section.bounds.y - bounds.height,
+        alignment::Vertical::Baseline => {
+            let baseline = entry
+                .buffer
+                .layout_runs()
+                .next()
+                .map_or(0.0, |run| run.line_y);
+
+            section.bounds.y - baseline * ratio
+        }
+    };
+
+    Point::new(x, y)
+}
+
+fn is_bounded_rtl(section: &Text<'_>, entry: &Entry) -> bool {
+    section.bounds.width.is_finite()
+        && entry
+            .buffer
+            .layout_runs()
+            .next()
+            .map_or(false, |run| run.rtl)
+}
+
+fn ink_offset(section: &Text<'_>, entry: &Entry) -> f32 {
+    if !is_bounded_rtl(section, entry) {
+        return 0.0;
+    }
+
+    // The glyphs of right-to-left lines are laid out against the right edge
+    // of the buffer, instead of its origin
+    let left = entry
+        .buffer
+        .layout_runs()
+        .flat_map(|run| run.glyphs.iter())
+        .map(|glyph| glyph.x)
+        .fold(f32::INFINITY, f32::min);
+
+    if left.is_finite() {
+        left
+    } else {
+        0.0
+    }
+}
+
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.split_inclusive('\n').scan(0, |offset, line| {
+            *offset += line.len();
+
+            Some(*offset)
+        }))
+        .collect()
+}
+
+fn fill_background(
+    quads: &mut quad::Batch,
+    section: &Text<'_>,
+    entry: &Entry,
+    scale_factor: f32,
+) {
+    let Some(color) = section.background else {
+        return;
+    };
+
+    let ratio = section
+        .scale_override
+        .map_or(1.0, |scale| scale / scale_factor);
+
+    let position = align(section, entry, ratio);
+
+    add_clipped(
+        quads,
+        section,
+        Rectangle {
+            x: position.x,
+            y: position.y,
+            width: entry.bounds.width * ratio,
+            height: entry.bounds.height * ratio,
+        },
+        &Background::Color(Color {
+            a: color.a * section.opacity,
+            ..color
+        }),
+    );
+}
+
+fn select(
+    quads: &mut quad::Batch,
+    section: &Text<'_>,
+    entry: &Entry,
+    scale_factor: f32,
+) {
+    if section.selections.is_empty() {
+        return;
+    }
+
+    let ratio = section
+        .scale_override
+        .map_or(1.0, |scale| scale / scale_factor);
+
+    let position = align(section, entry, ratio);
+
+    let line_height = entry.buffer.metrics().line_height;
+    let line_starts = line_starts(entry.content(section.content));
+
+    for (i, run) in entry.buffer.layout_runs().enumerate() {
+        let line_start = line_starts.get(run.line_i).copied().unwrap_or(0);
+        let top = i as f32 * line_height + entry.paragraphs.offset(run.line_i);
+
+        for selection in section.selections {
+            let range = entry.expand(selection.range.start)
+                ..entry.expand(selection.range.end);
+
+            // Glyphs are in visual order, so a selection may need multiple
+            // rectangles in a single line of bidirectional text
+            let mut rectangles: Vec<(f32, f32)> = Vec::new();
+
+            for glyph in run.glyphs {
+                let start = line_start + glyph.start;
+                let end = line_start + glyph.end;
+
+                if start >= range.end || end <= range.start {
+                    continue;
+                }
+
+                match rectangles.last_mut() {
+                    Some((_, right)) if (glyph.x - *right).abs() < 0.5 => {
+                        *right = glyph.x + glyph.w;
+                    }
+                    _ => rectangles.push((glyph.x, glyph.x + glyph.w)),
+                }
+            }
+
+            let background = Background::Color(Color {
+                a: selection.color.a * section.opacity,
+                ..selection.color
+            });
+
+            for (left, right) in rectangles {
+                add_clipped(
+                    quads,
+                    section,
+                    Rectangle {
+                        x: position.x + left * ratio,
+                        y: position.y + top * ratio,
+                        width: (right - left) * ratio,
+                        height: line_height * ratio,
+                    },
+                    &background,
+                );
+            }
+        }
+    }
+}
+
+fn decorate(
+    quads: &mut quad::Batch,
+    font_system: &mut glyphon::FontSystem,
+    section: &Text<'_>,
+    entry: &Entry,
+    color: Color,
+    scale_factor: f32,
+) {
+    let line = match section.decoration {
+        primitive::Decoration::None => return,
+        primitive::Decoration::Underline(line)
+        | primitive::Decoration::Strikethrough(line) => line,
+    };
+
+    let ratio = section
+        .scale_override
+        .map_or(1.0, |scale| scale / scale_factor);
+
+    let position = align(section, entry, ratio);
+    let background = Background::Color(Color {
+        a: color.a * section.opacity,
+        ..color
+    });
+
+    for run in entry.buffer.layout_runs() {
+        let is_blank = |glyph: &glyphon::LayoutGlyph| {
+            run.text[glyph.start..glyph.end]
+                .chars()
+                .all(char::is_whitespace)
+        };
+
+        // Glyphs are in visual order, so trailing whitespace is found by
+        // its logical position instead
+        let visible_end = if line.trailing_whitespace {
+            usize::MAX
+        } else {
+            run.glyphs
+                .iter()
+                .filter(|glyph| !is_blank(*glyph))
+                .map(|glyph| glyph.end)
+                .max()
+                .unwrap_or(0)
+        };
+
+        let mut glyphs = run
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.start < visible_end)
+            .peekable();
+
+        let Some(first) = glyphs.peek().copied() else {
+            continue;
+        };
+
+        let (start, end) =
+            glyphs.fold((f32::INFINITY, 0.0f32), |(start, end), glyph| {
+                (start.min(glyph.x), end.max(glyph.x + glyph.w))
+            });
+
+        // Font units are y-up and relative to the baseline
+        let (underline, strikethrough, thickness) = font_system
+            .get_font(first.font_id)
+            .map(|font| {
+                let metrics = font.as_swash().metrics(&[]);
+                let scale = section.size / f32::from(metrics.units_per_em);
+
+                (
+                    -metrics.underline_offset * scale,
+                    -metrics.strikeout_offset * scale,
+                    metrics.stroke_size * scale,
+                )
+            })
+            .unwrap_or((
+                section.size / 10.0,
+                -section.size / 4.0,
+                section.size / 14.0,
+            ));
+
+        let thickness = line.thickness.unwrap_or(thickness);
+
+        let offset = line.offset.unwrap_or(match section.decoration {
+            primitive::Decoration::Strikethrough(_) => {
+                strikethrough - thickness / 2.0
+            }
+            _ => underline,
+        });
+
+        let offset = offset * ratio;
+        let thickness = (thickness * ratio).max(1.0 / scale_factor);
+
+        add_clipped(
+            quads,
+            section,
+            Rectangle {
+                x: position.x + start * ratio,
+                y: position.y
+                    + (run.line_y + entry.paragraphs.offset(run.line_i))
+                        * ratio
+                    + offset,
+                width: (end - start) * ratio,
+                height: thickness,
+            },
+            &background,
+        );
+    }
+}
+
+fn add_clipped(
+    quads: &mut quad::Batch,
+    section: &Text<'_>,
+    bounds: Rectangle,
+    background: &Background,
+) {
+    let bounds = match section.clip_bounds {
+        Some(clip_bounds) => match bounds.intersection(&clip_bounds) {
+            Some(bounds) => bounds,
+            None => return,
+        },
+        None => bounds,
+    };
+
+    quads.add(
+        Quad {
+            position: [bounds.x, bounds.y],
+            size: [bounds.width, bounds.height],
+            border_color: color::pack(Color::TRANSPARENT),
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+        },
+        background,
+    );
+}
+
+fn resolve_color(color: Color, default: Option<Color>) -> Color {
+    match default {
+        Some(default) if color.a == 0.0 => default,
+        _ => color,
+    }
+}
+
+fn layers(
+    section: &Text<'_>,
+    default_color: Option<Color>,
+) -> Vec<(Vector, Color, Option<Paint>)> {
+    let with_opacity = |color: Color| Color {
+        a: color.a * section.opacity,
+        ..color
+    };
+
+    // Shadows and strokes are drawn with their own color, even around
+    // colored glyphs
+    let shadows = section.shadow.map(shadow_layers).unwrap_or_default();
+    let strokes = section.stroke.map(stroke_layers).unwrap_or_default();
+
+    let fill = with_opacity(resolve_color(section.color, default_color));
+
+    // The default color already fades, but colored glyphs need to as well
+    let faded =
+        (section.opacity < 1.0).then_some(Paint::Opacity(section.opacity));
+
+    let tinted = |(offset, color): (Vector, Color)| {
+        let color = with_opacity(color);
+
+        (offset, color, Some(Paint::Tint(color)))
+    };
+
+    shadows
+        .into_iter()
+        .map(tinted)
+        .chain(strokes.into_iter().map(tinted))
+        .chain(std::iter::once((Vector::ZERO, fill, faded)))
+        .collect()
+}
+
+fn shadow_layers(shadow: primitive::Shadow) -> Vec<(Vector, Color)> {
+    if shadow.blur <= 0.0 {
+        return vec![(shadow.offset, shadow.color)];
+    }
+
+    // Four overlapping copies that add up to the opacity of the shadow
+    let spread = shadow.blur / 2.0;
+    let alpha = 1.0 - (1.0 - shadow.color.a).powf(0.25);
+
+    [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+        .into_iter()
+        .map(|(x, y)| {
+            (
+                shadow.offset + Vector::new(x * spread, y * spread),
+                Color {
+                    a: alpha,
+                    ..shadow.color
+                },
+            )
+        })
+        .collect()
+}
+
+fn stroke_layers(stroke: primitive::Stroke) -> Vec<(Vector, Color)> {
+    if stroke.width <= 0.0 {
+        return Vec::new();
+    }
+
+    // Eight copies around the glyphs, at the same distance in every direction
+    let diagonal = stroke.width * std::f32::consts::FRAC_1_SQRT_2;
+
+    [
+        (-stroke.width, 0.0),
+        (stroke.width, 0.0),
+        (0.0, -stroke.width),
+        (0.0, stroke.width),
+        (-diagonal, -diagonal),
+        (diagonal, -diagonal),
+        (-diagonal, diagonal),
+        (diagonal, diagonal),
+    ]
+    .into_iter()
+    .map(|(x, y)| (Vector::new(x, y), stroke.color))
+    .collect()
+}
+
+fn has_font_signature(bytes: &[u8]) -> bool {
+    const SIGNATURES: [&[u8]; 5] =
+        [b"\x00\x01\x00\x00", b"OTTO", b"true", b"typ1", b"ttcf"];
+
+    SIGNATURES
+        .iter()
+        .any(|signature| bytes.starts_with(signature))
+}
+
+fn to_attrs(font: Font) -> glyphon::Attrs<'static> {
+    glyphon::Attrs::new()
+        .family(to_family(font.family))
+        .weight(to_weight(font.weight))
+        .stretch(to_stretch(font.stretch))
+        .style(to_style(font.style))
+}
+
+fn to_color(color: Color) -> glyphon::Color {
+    // glyphon blends with straight alpha, so colors are not premultiplied
+    let [r, g, b, a] = color::pack(color).components();
+
+    glyphon::Color::rgba(
+        (r * 255.0).round() as u8,
+        (g * 255.0).round() as u8,
+        (b * 255.0).round() as u8,
+        (a * 255.0).round() as u8,
+    )
+}
+
+fn to_family(family: font::Family) -> glyphon::Family<'static> {
+    match family {
+        font::Family::Name(name) => glyphon::Family::Name(name),
+        font::Family::SansSerif => glyphon::Family::SansSerif,
+        font::Family::Serif => glyphon::Family::Serif,
+        font::Family::Cursive => glyphon::Family::Cursive,
+        font::Family::Fantasy => glyphon::Family::Fantasy,
+        font::Family::Monospace => glyphon::Family::Monospace,
+    }
+}
+
+fn to_weight(weight: font::Weight) -> glyphon::Weight {
+    match weight {
+        font::Weight::Thin => glyphon::Weight::THIN,
+        font::Weight::ExtraLight => glyphon::Weight::EXTRA_LIGHT,
+        font::Weight::Light => glyphon::Weight::LIGHT,
+        font::Weight::Normal => glyphon::Weight::NORMAL,
+        font::Weight::Medium => glyphon::Weight::MEDIUM,
+        font::Weight::Semibold => glyphon::Weight::SEMIBOLD,
+        font::Weight::Bold => glyphon::Weight::BOLD,
+        font::Weight::ExtraBold => glyphon::Weight::EXTRA_BOLD,
+        font::Weight::Black => glyphon::Weight::BLACK,
+    }
+}
+
+fn to_stretch(stretch: font::Stretch) -> glyphon::Stretch {
+    match stretch {
+        font::Stretch::UltraCondensed => glyphon::Stretch::UltraCondensed,
+        font::Stretch::ExtraCondensed => glyphon::Stretch::ExtraCondensed,
+        font::Stretch::Condensed => glyphon::Stretch::Condensed,
+        font::Stretch::SemiCondensed => glyphon::Stretch::SemiCondensed,
+        font::Stretch::Normal => glyphon::Stretch::Normal,
+        font::Stretch::SemiExpanded => glyphon::Stretch::SemiExpanded,
+        font::Stretch::Expanded => glyphon::Stretch::Expanded,
+        font::Stretch::ExtraExpanded => glyphon::Stretch::ExtraExpanded,
+        font::Stretch::UltraExpanded => glyphon::Stretch::UltraExpanded,
+    }
+}
+
+fn to_style(style: font::Style) -> glyphon::Style {
+    match style {
+        font::Style::Normal => glyphon::Style::Normal,
+        font::Style::Italic => glyphon::Style::Italic,
+        font::Style::Oblique => glyphon::Style::Oblique,
+    }
+}
+
+fn to_wrap(wrap: primitive::Wrap) -> glyphon::Wrap {
+    match wrap {
+        primitive::Wrap::Word => glyphon::Wrap::Word,
+        primitive::Wrap::Glyph => glyphon::Wrap::Glyph,
+        primitive::Wrap::None => glyphon::Wrap::None,
+    }
+}
+
+fn to_shaping(shaping: Shaping) -> glyphon::Shaping {
+    match shaping {
+        Shaping::Basic => glyphon::Shaping::Basic,
+        Shaping::Advanced => glyphon::Shaping::Advanced,
+    }
+}
+
+struct Cache {
+    entries: FxHashMap<KeyHash, Entry>,
+    aliases: FxHashMap<KeyHash, KeyHash>,
+    layouts: FxHashMap<KeyHash, KeyHash>,
+    empty: FxHashMap<KeyHash, Entry>,
+    recently_measured: FxHashSet<KeyHash>,
+    recently_drawn: FxHashSet<KeyHash>,
+    preheated: FxHashSet<KeyHash>,
+    pinned: FxHashMap<KeyHash, Pinned>,
+    next_pin: KeyHash,
+    cells: FxHashMap<(Font, u32), Size>,
+    hasher: HashBuilder,
+    replacement: Option<char>,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    clock: u64,
+    measuring: CacheUsage,
+    drawing: CacheUsage,
+}
+
+struct Entry {
+    buffer: glyphon::Buffer,
+    bounds: Size,
+    memory: usize,
+    last_used: u64,
+    colored: bool,
+    variants: Vec<Variant>,
+    substitutes: Option<Substitutes>,
+    tabs: Option<Tabs>,
+    paragraphs: Paragraphs,
+}
+
+impl Entry {
+    fn update(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        key: Key<'_>,
+        replacement: Option<char>,
+    ) {
+        // Everything derived from the buffer is stale once it changes
+        self.substitutes = replacement.and_then(|replacement| {
+            substitute(&mut self.buffer, font_system, key, replacement)
+        });
+
+        self.paragraphs = Paragraphs::new(&self.buffer, key.paragraph_spacing);
+        self.bounds = self.paragraphs.measure(&self.buffer);
+        self.memory = estimate_memory(&self.buffer);
+        self.colored = is_colored(&self.buffer);
+        self.variants.clear();
+        self.tabs = Tabs::new(key);
+    }
+
+    // The buffer is shaped from the expanded contents, so offsets into the
+    // original contents need to be mapped before looking them up
+    fn content<'a>(&'a self, content: &'a str) -> &'a str {
+        self.tabs.as_ref().map_or(content, |tabs| &tabs.content)
+    }
+
+    fn expand(&self, index: usize) -> usize {
+        self.tabs.as_ref().map_or(index, |tabs| tabs.expand(index))
+    }
+
+    fn collapse(&self, content: &str, details: HitDetails) -> HitDetails {
+        let Some(tabs) = &self.tabs else {
+            return details;
+        };
+
+        let Some(line) = self
+            .buffer
+            .layout_runs()
+            .nth(details.line)
+            .map(|run| run.line_i)
+        else {
+            return details;
+        };
+
+        let start = |content: &str| {
+            line_starts(content).get(line).copied().unwrap_or_default()
+        };
+
+        let index = tabs.collapse(start(&tabs.content) + details.index);
+
+        HitDetails {
+            index: index.saturating_sub(start(content)),
+            ..details
+        }
+    }
+
+    fn painted(&self, paint: Option<Paint>) -> &glyphon::Buffer {
+        paint
+            .and_then(|paint| {
+                self.variants.iter().find(|variant| variant.paint == paint)
+            })
+            .map_or(&self.buffer, |variant| &variant.buffer)
+    }
+}
+
+// The replacement of the missing glyphs of a buffer, drawn over them
+struct Substitutes {
+    buffer: glyphon::Buffer,
+    positions: Vec<Point>,
+}
+
+// The contents of a buffer with its tabs expanded to the next tab stop
+struct Tabs {
+    content: String,
+    // The offset of every tab in the original contents, and the number of
+    // spaces it was expanded to
+    stops: Vec<(usize, usize)>,
+}
+
+impl Tabs {
+    fn new(key: Key<'_>) -> Option<Self> {
+        let tab_width = key.tab_width?.max(1);
+
+        let mut content = String::with_capacity(key.content.len());
+        let mut stops = Vec::new();
+        let mut column = 0;
+
+        for (i, c) in key.content.char_indices() {
+            match c {
+                '\t' => {
+                    let spaces = tab_width - column % tab_width;
+
+                    content.extend(std::iter::repeat(' ').take(spaces));
+                    stops.push((i, spaces));
+                    column += spaces;
+                }
+                '\n' => {
+                    content.push(c);
+                    column = 0;
+                }
+                _ => {
+                    content.push(c);
+                    column += 1;
+                }
+            }
+        }
+
+        (!stops.is_empty()).then_some(Self { content, stops })
+    }
+
+    fn expand(&self, index: usize) -> usize {
+        index
+            + self
+                .stops
+                .iter()
+                .take_while(|(tab, _)| *tab < index)
+                .map(|(_, spaces)| spaces - 1)
+                .sum::<usize>()
+    }
+
+    fn collapse(&self, index: usize) -> usize {
+        let mut shift = 0;
+
+        for &(tab, spaces) in &self.stops {
+            let start = tab + shift;
+
+            if index <= start {
+                break;
+            }
+
+            // An offset inside of the spaces of a tab snaps to its closest
+            // edge
+            if index < start + spaces {
+                return if index - start <= spaces / 2 {
+                    tab
+                } else {
+                    tab + 1
+                };
+            }
+
+            shift += spaces - 1;
+        }
+
+        index - shift
+    }
+
+    fn spans(&self, spans: &[primitive::Span]) -> Vec<primitive::Span> {
+        spans
+            .iter()
+            .map(|span| primitive::Span {
+                range: self.expand(span.range.start)
+                    ..self.expand(span.range.end),
+                ..span.clone()
+            })
+            .collect()
+    }
+}
+
+// The paragraph of every line of a buffer, which moves it down by the
+// spacing of all the paragraphs before it. A paragraph ends at a blank line.
+#[derive(Debug, Default)]
+struct Paragraphs {
+    spacing: f32,
+    indices: Vec<usize>,
+}
+
+impl Paragraphs {
+    fn new(buffer: &glyphon::Buffer, spacing: f32) -> Self {
+        if spacing <= 0.0 {
+            return Self::default();
+        }
+
+        let mut paragraph = 0;
+        let mut has_text = false;
+        let mut is_after_blank = false;
+
+        let indices = buffer
+            .lines
+            .iter()
+            .map(|line| {
+                let is_blank = line.text().trim().is_empty();
+
+                if !is_blank {
+                    if has_text && is_after_blank {
+                        paragraph += 1;
+                    }
+
+                    has_text = true;
+                }
+
+                is_after_blank = is_blank;
+
+                paragraph
+            })
+            .collect();
+
+        Self { spacing, indices }
+    }
+
+    fn index(&self, line: usize) -> usize {
+        self.indices.get(line).copied().unwrap_or_default()
+    }
+
+    fn offset(&self, line: usize) -> f32 {
+        self.index(line) as f32 * self.spacing
+    }
+
+    // The offset of the last visible line, which every paragraph before it
+    // adds to the height of the buffer
+    fn height(&self, buffer: &glyphon::Buffer) -> f32 {
+        buffer
+            .layout_runs()
+            .last()
+            .map_or(0.0, |run| self.offset(run.line_i))
+    }
+
+    fn measure(&self, buffer: &glyphon::Buffer) -> Size {
+        let bounds = measure(buffer);
+
+        Size::new(bounds.width, bounds.height + self.height(buffer))
+    }
+
+    // glyphon draws a whole buffer at a single position, so every paragraph
+    // is drawn as its own area of the buffer, moved down by its offset and
+    // clipped to its own lines
+    fn areas(
+        &self,
+        buffer: &glyphon::Buffer,
+        top: f32,
+        scale: f32,
+        bounds: glyphon::TextBounds,
+    ) -> Vec<(f32, glyphon::TextBounds)> {
+        if self.indices.is_empty() {
+            return vec![(top, bounds)];
+        }
+
+        let line_height = buffer.metrics().line_height * scale;
+
+        // The paragraph of each area, and the visual lines it spans
+        let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+
+        for (i, run) in buffer.layout_runs().enumerate() {
+            let paragraph = self.index(run.line_i);
+
+            match spans.last_mut() {
+                Some((last, _, end)) if *last == paragraph => *end = i + 1,
+                _ => spans.push((paragraph, i, i + 1)),
+            }
+        }
+
+        let count = spans.len();
+
+        spans
+            .into_iter()
+            .enumerate()
+            .map(|(i, (paragraph, start, end))| {
+                let top = top + paragraph as f32 * self.spacing * scale;
+
+                // The first and last paragraphs may still overflow their
+                // lines, like the rest of the text
+                let clip_top = if i == 0 {
+                    bounds.top
+                } else {
+                    bounds.top.max((top + start as f32 * line_height) as i32)
+                };
+
+                let clip_bottom = if i + 1 == count {
+                    bounds.bottom
+                } else {
+                    bounds.bottom.min((top + end as f32 * line_height) as i32)
+                };
+
+                (
+                    top,
+                    glyphon::TextBounds {
+                        left: bounds.left,
+                        top: clip_top,
+                        right: bounds.right,
+                        bottom: clip_bottom.max(clip_top),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+// A copy of a buffer with the colors of its glyphs painted over
+struct Variant {
+    paint: Paint,
+    buffer: glyphon::Buffer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paint {
+    // Every glyph is drawn with the color, keeping only its own alpha
+    Tint(Color),
+    // Every colored glyph keeps its color, but fades with the opacity
+    Opacity(f32),
+}
+
+// Pinned buffers own their contents, since appending to them changes what
+// they were keyed by
+struct Pinned {
+    content: String,
+    spans: Vec<primitive::Span>,
+    key: Key<'static>,
+    entry: Entry,
+}
+
+impl Pinned {
+    fn reshape(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        replacement: Option<char>,
+    ) {
+        let key = Key {
+            content: &self.content,
+            spans: &self.spans,
+            ..self.key
+        };
+
+        self.entry.buffer = shape(font_system, key);
+        self.entry.update(font_system, key, replacement);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Purpose {
+    Measuring,
+    Drawing,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type RandomHashBuilder = twox_hash::RandomXxHashBuilder64;
+
+#[cfg(target_arch = "wasm32")]
+type RandomHashBuilder = std::hash::BuildHasherDefault<twox_hash::XxHash64>;
+
+#[derive(Clone)]
+enum HashBuilder {
+    Random(RandomHashBuilder),
+    Seeded(u64),
+    Custom(CacheHasher),
+}
+
+impl HashBuilder {
+    fn new(seed: Option<u64>) -> Self {
+        match seed {
+            Some(seed) => Self::Seeded(seed),
+            None => Self::Random(RandomHashBuilder::default()),
+        }
+    }
+}
+
+impl BuildHasher for HashBuilder {
+    type Hasher = KeyHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        match self {
+            Self::Random(random) => KeyHasher::Xx(random.build_hasher()),
+            Self::Seeded(seed) => {
+                KeyHasher::Xx(twox_hash::XxHash64::with_seed(*seed))
+            }
+            Self::Custom(hasher) => KeyHasher::Custom((hasher.0)()),
+        }
+    }
+}
+
+enum KeyHasher {
+    Xx(twox_hash::XxHash64),
+    Custom(Box<dyn Hasher>),
+}
+
+impl Hasher for KeyHasher {
+    fn finish(&self) -> u64 {
+        match self {
+            Self::Xx(hasher) => hasher.finish(),
+            Self::Custom(hasher) => hasher.finish(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Xx(hasher) => hasher.write(bytes),
+            Self::Custom(hasher) => hasher.write(bytes),
+        }
+    }
+}
+
+impl Cache {
+    fn new(
+        max_entries: Option<usize>,
+        max_bytes: Option<usize>,
+        hasher: HashBuilder,
+        replacement: Option<char>,
+    ) -> Self {
+        Self {
+            entries: FxHashMap::default(),
+            aliases: FxHashMap::default(),
+            layouts: FxHashMap::default(),
+            empty: FxHashMap::default(),
+            recently_measured: FxHashSet::default(),
+            recently_drawn: FxHashSet::default(),
+            preheated: FxHashSet::default(),
+            pinned: FxHashMap::default(),
+            next_pin: 0,
+            cells: FxHashMap::default(),
+            hasher,
+            replacement,
+            max_entries,
+            max_bytes,
+            clock: 0,
+            measuring: CacheUsage::default(),
+            drawing: CacheUsage::default(),
+        }
+    }
+
+    fn clear(&mut self, font_system: &mut glyphon::FontSystem) {
+        let next_pin = self.next_pin;
+        let mut pinned = std::mem::take(&mut self.pinned);
+
+        *self = Self::new(
+            self.max_entries,
+            self.max_bytes,
+            self.hasher.clone(),
+            self.replacement,
+        );
+
+        // Pinned buffers are shaped again instead, so their handles stay
+        // valid with the new fonts
+        for pinned in pinned.values_mut() {
+            pinned.reshape(font_system, self.replacement);
+        }
+
+        // Stale handles must never point to buffers pinned later on
+        self.pinned = pinned;
+        self.next_pin = next_pin;
+    }
+
+    fn get(&self, key: &KeyHash) -> Option<&Entry> {
+        self.entries
+            .get(key)
+            .or_else(|| self.pinned.get(key).map(|pinned| &pinned.entry))
+            .or_else(|| self.empty.get(key))
+    }
+
+    fn touch(&mut self, hash: KeyHash) -> bool {
+        let Some(pinned) = self.pinned.get_mut(&hash) else {
+            return false;
+        };
+
+        self.clock += 1;
+        self.drawing.hits += 1;
+
+        pinned.entry.last_used = self.clock;
+
+        true
+    }
+
+    fn pin(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        key: Key<'_>,
+    ) -> Handle {
+        let key = sanitize(key);
+        let mut buffer = shape(font_system, key);
+
+        let substitutes = self.replacement.and_then(|replacement| {
+            substitute(&mut buffer, font_system, key, replacement)
+        });
+
+        // Handles are counted instead of hashed, so pinning the same text
+        // twice gives two buffers that can grow independently
+        let handle = self.next_pin;
+        self.next_pin += 1;
+        self.clock += 1;
+
+        let paragraphs = Paragraphs::new(&buffer, key.paragraph_spacing);
+
+        let _ = self.pinned.insert(
+            handle,
+            Pinned {
+                content: key.content.to_owned(),
+                spans: key.spans.to_vec(),
+                key: detach(key),
+                entry: Entry {
+                    bounds: paragraphs.measure(&buffer),
+                    memory: estimate_memory(&buffer),
+                    colored: is_colored(&buffer),
+                    buffer,
+                    last_used: self.clock,
+                    variants: Vec::new(),
+                    substitutes,
+                    tabs: Tabs::new(key),
+                    paragraphs,
+                },
+            },
+        );
+
+        Handle(handle)
+    }
+
+    fn append(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        handle: Handle,
+        content: &str,
+        font: Font,
+    ) -> bool {
+        let Some(pinned) = self.pinned.get_mut(&handle.0) else {
+            return false;
+        };
+
+        let start = pinned.content.len();
+        pinned.content.push_str(content);
+
+        if font != pinned.key.font {
+            pinned.spans.push(primitive::Span {
+                range: start..pinned.content.len(),
+                font: Some(font),
+                color: None,
+            });
+        }
+
+        let key = Key {
+            content: &pinned.content,
+            spans: &pinned.spans,
+            ..pinned.key
+        };
+
+        let buffer = &mut pinned.entry.buffer;
+
+        // Truncated and colored contents depend on all of the text, and tabs
+        // depend on the column they start at, so only plain text can be
+        // shaped incrementally
+        let is_incremental = !key.ellipsis
+            && key.max_lines.is_none()
+            && key.gradient.is_none()
+            && key.fade.is_none()
+            && !(key.tab_width.is_some() && content.contains('\t'));
+
+        if is_incremental {
+            extend(buffer, font_system, key, content, font);
+        } else {
+            *buffer = shape(font_system, key);
+        }
+
+        pinned.entry.update(font_system, key, self.replacement);
+
+        self.clock += 1;
+        pinned.entry.last_used = self.clock;
+
+        true
+    }
+
+    fn paint(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        hash: KeyHash,
+        paint: Paint,
+    ) {
+        // Only a few colors of the same text are usually drawn at once
+        const MAX_VARIANTS: usize = 4;
+
+        let Some(entry) = self.entries.get_mut(&hash).or_else(|| {
+            self.pinned.get_mut(&hash).map(|pinned| &mut pinned.entry)
+        }) else {
+            return;
+        };
+
+        if !entry.colored
+            || entry.variants.iter().any(|variant| variant.paint == paint)
+        {
+            return;
+        }
+
+        if entry.variants.len() >= MAX_VARIANTS {
+            let oldest = entry.variants.remove(0);
+
+            entry.memory -= estimate_memory(&oldest.buffer);
+        }
+
+        let buffer = repaint(font_system, &entry.buffer, paint);
+
+        entry.memory += estimate_memory(&buffer);
+        entry.variants.push(Variant { paint, buffer });
+    }
+
+    fn invalidate_faces(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        faces: &[glyphon::fontdb::ID],
+    ) {
+        self.entries.retain(|_, entry| {
+            !entry.buffer.layout_runs().any(|run| {
+                run.glyphs
+                    .iter()
+                    .any(|glyph| faces.contains(&glyph.font_id))
+            })
+        });
+
+        self.aliases
+            .retain(|_, value| self.entries.contains_key(value));
+        self.layouts
+            .retain(|_, value| self.entries.contains_key(value));
+        self.cells.clear();
+
+        for pinned in self.pinned.values_mut() {
+            let uses_faces = pinned.entry.buffer.layout_runs().any(|run| {
+                run.glyphs
+                    .iter()
+                    .any(|glyph| faces.contains(&glyph.font_id))
+            });
+
+            if uses_faces {
+                pinned.reshape(font_system, self.replacement);
+            }
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            aliases: self.aliases.len(),
+            memory: self.memory()
+                + self
+                    .pinned
+                    .values()
+                    .map(|pinned| pinned.entry.memory)
+                    .sum::<usize>(),
+            measuring: self.measuring,
+            drawing: self.drawing,
+        }
+    }
+
+    fn allocate(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        key: Key<'_>,
+        purpose: Purpose,
+    ) -> (KeyHash, &mut Entry) {
+        let key = sanitize(key);
+
+        if key.content.is_empty() {
+            return self.allocate_empty(font_system, key, purpose);
+        }
+
+        let hash = key.hash(self.hasher.build_hasher());
+
+        // Truncated contents and gradients depend on the bounds they were
+        // laid out with, so they always need to be shaped again
+        let is_reflowable =
+            !key.ellipsis && key.max_lines.is_none() && key.gradient.is_none();
+
+        let layout = Key {
+            bounds: Size::ZERO,
+            ..key
+        }
+        .hash(self.hasher.build_hasher());
+
+        // A buffer with the same contents but other bounds can be wrapped
+        // again without shaping, as long as it is not in use
+        let reflowed = self
+            .layouts
+            .get(&layout)
+            .copied()
+            .filter(|old| {
+                is_reflowable
+                    && !self.entries.contains_key(&hash)
+                    && !self.aliases.contains_key(&hash)
+                    && !self.recently_measured.contains(old)
+                    && !self.recently_drawn.contains(old)
+                    && !self.preheated.contains(old)
+            })
+            .and_then(|old| {
+                let entry = self.entries.remove(&old)?;
+
+                let _ = self.layouts.remove(&layout);
+                self.aliases.retain(|_, value| *value != old);
+
+                Some(entry.buffer)
+            });
+
+        let (recently_used, usage) = match purpose {
+            Purpose::Measuring => {
+                (&mut self.recently_measured, &mut self.measuring)
+            }
+            Purpose::Drawing => (&mut self.recently_drawn, &mut self.drawing),
+        };
+
+        self.clock += 1;
+
+        if let Some(hash) = self.aliases.get(&hash) {
+            let _ = recently_used.insert(*hash);
+            usage.hits += 1;
+
+            let entry = self.entries.get_mut(hash).unwrap();
+            entry.last_used = self.clock;
+
+            return (*hash, entry);
+        }
+
+        if let hash_map::Entry::Vacant(entry) = self.entries.entry(hash) {
+            usage.misses += 1;
+
+            let mut buffer = match reflowed {
+                Some(mut buffer) => {
+                    buffer.set_size(
+                        font_system,
+                        key.bounds.width,
+                        key.bounds.height.max(key.line_height),
+                    );
+
+                    buffer
+                }
+                None => shape(font_system, key),
+            };
+
+            let substitutes = self.replacement.and_then(|replacement| {
+                substitute(&mut buffer, font_system, key, replacement)
+            });
+
+            let paragraphs = Paragraphs::new(&buffer, key.paragraph_spacing);
+            let bounds = paragraphs.measure(&buffer);
+            let memory = estimate_memory(&buffer);
+
+            let _ = entry.insert(Entry {
+                colored: is_colored(&buffer),
+                buffer,
+                bounds,
+                memory,
+                last_used: self.clock,
+                variants: Vec::new(),
+                substitutes,
+                tabs: Tabs::new(key),
+                paragraphs,
+            });
+
+            if is_reflowable {
+                let _ = self.layouts.insert(layout, hash);
+            }
+
+            // Truncated contents may change when laid out with other bounds
+            let is_truncated = key.ellipsis || key.max_lines.is_some();
+
+            for bounds in [
+                bounds,
+                Size {
+                    width: key.bounds.width,
+                    ..bounds
+                },
+            ] {
+                if key.bounds != bounds && !is_truncated {
+                    let _ = self.aliases.insert(
+                        Key { bounds, ..key }.hash(self.hasher.build_hasher()),
+                        hash,
+                    );
+                }
+            }
+        } else {
+            usage.hits += 1;
+        }
+
+        let _ = recently_used.insert(hash);
+
+        let entry = self.entries.get_mut(&hash).unwrap();
+        entry.last_used = self.clock;
+
+        (hash, entry)
+    }
+
+    fn allocate_empty(
+        &mut self,
+        font_system: &mut glyphon::FontSystem,
+        key: Key<'_>,
+        purpose: Purpose,
+    ) -> (KeyHash, &mut Entry) {
+        // Empty contents only depend on their metrics, so they share a
+        // buffer that is never shaped nor counted as a cache entry, but is
+        // still trimmed once unused
+        let mut hasher = self.hasher.build_hasher();
+
+        key.content.hash(&mut hasher);
+        key.size.to_bits().hash(&mut hasher);
+        key.line_height.to_bits().hash(&mut hasher);
+
+        let hash = hasher.finish();
+
+        let _ = match purpose {
+            Purpose::Measuring => self.recently_measured.insert(hash),
+            Purpose::Drawing => self.recently_drawn.insert(hash),
+        };
+
+        let entry = self.empty.entry(hash).or_insert_with(|| {
+            let metrics = glyphon::Metrics::new(key.size, key.line_height);
+            let mut buffer = glyphon::Buffer::new(font_system, metrics);
+
+            buffer.set_size(font_system, f32::INFINITY, key.line_height);
+
+            Entry {
+                buffer,
+                bounds: Size::new(0.0, key.line_height),
+                memory: 0,
+                last_used: 0,
+                colored: false,
+                variants: Vec::new(),
+                substitutes: None,
+                tabs: None,
+                paragraphs: Paragraphs::default(),
+            }
+        });
+
+        (hash, entry)
+    }
+
+    fn trim(&mut self, purpose: Purpose) {
+        // Preheated entries are kept until they are drawn for the first time
+        self.entries.retain(|key, _| {
+            self.recently_measured.contains(key)
+                || self.recently_drawn.contains(key)
+                || self.preheated.contains(key)
+        });
+        self.aliases.retain(|_, value| {
+            self.recently_measured.contains(value)
+                || self.recently_drawn.contains(value)
+                || self.preheated.contains(value)
+        });
+        self.empty.retain(|key, _| {
+            self.recently_measured.contains(key)
+                || self.recently_drawn.contains(key)
+                || self.preheated.contains(key)
+        });
+
+        self.evict();
+
+        self.preheated.retain(|key| {
+            self.entries.contains_key(key) || self.empty.contains_key(key)
+        });
+
+        match purpose {
+            Purpose::Measuring => {
+                self.recently_measured.clear();
+            }
+            Purpose::Drawing => {
+                self.recently_drawn.clear();
+            }
+        }
+    }
+
+    fn invalidate(&mut self, key: Key<'_>) -> bool {
+        let hash = sanitize(key).hash(self.hasher.build_hasher());
+        let hash = self.aliases.get(&hash).copied().unwrap_or(hash);
+
+        if self.entries.remove(&hash).is_none() {
+            return false;
+        }
+
+        self.aliases.retain(|_, value| *value != hash);
+        self.layouts.retain(|_, value| *value != hash);
+
+        let _ = self.recently_measured.remove(&hash);
+        let _ = self.recently_drawn.remove(&hash);
+        let _ = self.preheated.remove(&hash);
+
+        true
+    }
+
+    fn memory(&self) -> usize {
+        self.entries.values().map(|entry| entry.memory).sum()
+    }
+
+    fn evict(&mut self) {
+        self.evict_to(self.max_entries, self.max_bytes);
+    }
+
+    fn evict_to(
+        &mut self,
+        max_entries: Option<usize>,
+        max_bytes: Option<usize>,
+    ) {
+        let max_entries = max_entries.unwrap_or(usize::MAX);
+        let max_bytes = max_bytes.unwrap_or(usize::MAX);
+
+        let mut memory = self.memory();
+
+        if self.entries.len() <= max_entries && memory <= max_bytes {
+            return;
+        }
+
+        let mut by_age: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(hash, entry)| (entry.last_used, *hash))
+            .collect();
+
+        by_age.sort_unstable();
+
+        for (_, hash) in by_age {
+            if self.entries.len() <= max_entries && memory <= max_bytes {
+                break;
+            }
+
+            if let Some(entry) = self.entries.remove(&hash) {
+                memory -= entry.memory;
+            }
+        }
+
+        self.aliases
+            .retain(|_, value| self.entries.contains_key(value));
+        self.layouts
+            .retain(|_, value| self.entries.contains_key(value));
+    }
+}
+
+fn shape(
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+) -> glyphon::Buffer {
+    let tabs = Tabs::new(key);
+    let spans;
+
+    let key = match &tabs {
+        Some(tabs) => {
+            spans = tabs.spans(key.spans);
+
+            Key {
+                content: &tabs.content,
+                spans: &spans,
+                ..key
+            }
+        }
+        None => key,
+    };
+
+    let metrics = glyphon::Metrics::new(key.size, key.line_height);
+    let mut buffer = glyphon::Buffer::new(font_system, metrics);
+
+    buffer.set_wrap(font_system, to_wrap(key.wrap));
+
+    if key.ellipsis {
+        ellipsize(&mut buffer, font_system, key);
+    } else {
+        buffer.set_size(
+            font_system,
+            key.bounds.width,
+            key.bounds.height.max(key.line_height),
+        );
+
+        set_contents(&mut buffer, font_system, key);
+
+        if key.justify {
+            justify(&mut buffer, font_system);
+        }
+    }
+
+    let is_clamped = match key.max_lines {
+        Some(max_lines) => {
+            limit_lines(&mut buffer, font_system, key, max_lines)
+        }
+        None => false,
+    };
+
+    if let Some(gradient) = key.gradient {
+        fill(&mut buffer, font_system, gradient);
+    }
+
+    if let Some((width, color)) = key.fade.filter(|_| is_clamped) {
+        fade(&mut buffer, font_system, width, color);
+    }
+
+    buffer
+}
+
+fn extend(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+    content: &str,
+    font: Font,
+) {
+    let defaults = to_attrs(key.font);
+
+    // Appended content only needs a span if its font is not the default
+    let attrs_list = |text: &str, mut attrs_list: glyphon::AttrsList, start| {
+        if font != key.font && start < text.len() {
+            attrs_list.add_span(start..text.len(), to_attrs(font));
+        }
+
+        attrs_list
+    };
+
+    let mut lines = content.split('\n');
+
+    // The first line continues the last one, keeping its spans, while the
+    // rest are new
+    if let (Some(first), Some(last)) = (lines.next(), buffer.lines.last_mut()) {
+        let first = first.strip_suffix('\r').unwrap_or(first);
+
+        if !first.is_empty() {
+            let start = last.text().len();
+            let text = format!("{}{first}", last.text());
+            let list = attrs_list(&text, last.attrs_list().clone(), start);
+
+            let _ = last.set_text(text, list);
+        }
+    }
+
+    for line in lines {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        buffer.lines.push(glyphon::BufferLine::new(
+            line,
+            attrs_list(line, glyphon::AttrsList::new(defaults), 0),
+            to_shaping(key.shaping),
+        ));
+    }
+
+    if key.justify {
+        justify(buffer, font_system);
+    } else {
+        // Only the lines that changed are shaped again
+        buffer.shape_until_scroll(font_system);
+    }
+}
+
+fn detach(key: Key<'_>) -> Key<'static> {
+    Key {
+        content: "",
+        content_id: key.content_id,
+        spans: &[],
+        size: key.size,
+        line_height: key.line_height,
+        font: key.font,
+        bounds: key.bounds,
+        shaping: key.shaping,
+        ellipsis: key.ellipsis,
+        justify: key.justify,
+        max_lines: key.max_lines,
+        fade: key.fade,
+        wrap: key.wrap,
+        gradient: key.gradient,
+        language: key.language,
+        tab_width: key.tab_width,
+        paragraph_spacing: key.paragraph_spacing,
+    }
+}
+
+fn sanitize(key: Key<'_>) -> Key<'_> {
+    const DEFAULT_SIZE: f32 = 16.0;
+    const DEFAULT_LINE_HEIGHT: f32 = 1.3;
+
+    // A single bad layout value must not poison the cache forever
+    let size = if key.size.is_finite() && key.size > 0.0 {
+        key.size
+    } else {
+        log::warn!("Invalid text size {}, using a default instead", key.size);
+
+        DEFAULT_SIZE
+    };
+
+    let line_height = if key.line_height.is_finite() && key.line_height > 0.0 {
+        key.line_height
+    } else {
+        log::warn!(
+            "Invalid line height {}, using a default instead",
+            key.line_height
+        );
+
+        size * DEFAULT_LINE_HEIGHT
+    };
+
+    let paragraph_spacing = if key.paragraph_spacing.is_finite() {
+        key.paragraph_spacing.max(0.0)
+    } else {
+        0.0
+    };
+
+    // Unbounded measurements of the same text share the same entry
+    Key {
+        size,
+        line_height,
+        paragraph_spacing,
+        bounds: Size::new(
+            unbounded(key.bounds.width).max(0.0),
+            unbounded(key.bounds.height).max(0.0),
+        ),
+        ..key
+    }
+}
+
+fn empty_bounds(key: Key<'_>) -> Option<Size> {
+    key.content
+        .is_empty()
+        .then(|| Size::new(0.0, sanitize(key).line_height))
+}
+
+fn visible_lines(height: f32, line_height: f32) -> usize {
+    // Partially visible lines do not count, so bounds shorter than a line
+    // have none
+    (height / line_height).floor() as usize
+}
+
+fn unbounded(value: f32) -> f32 {
+    if value.is_nan() || value >= f32::MAX {
+        f32::INFINITY
+    } else {
+        value
+    }
+}
+
+fn estimate_memory(buffer: &glyphon::Buffer) -> usize {
+    // Every glyph is both shaped and laid out
+    let glyph_size = std::mem::size_of::<glyphon::ShapeGlyph>()
+        + std::mem::size_of::<glyphon::LayoutGlyph>();
+
+    let lines: usize = buffer
+        .lines
+        .iter()
+        .map(|line| {
+            let glyphs: usize =
+                line.layout_opt().as_ref().map_or(0, |layout| {
+                    layout.iter().map(|line| line.glyphs.len()).sum()
+                });
+
+            line.text().len() + glyphs * glyph_size
+        })
+        .sum();
+
+    std::mem::size_of::<glyphon::Buffer>() + lines
+}
+
+fn set_contents(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+) {
+    if key.spans.is_empty() {
+        buffer.set_text(
+            font_system,
+            key.content,
+            to_attrs(key.font),
+            to_shaping(key.shaping),
+        );
+    } else {
+        set_rich_text(buffer, font_system, key);
+    }
+}
+
+fn substitute(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+    replacement: char,
+) -> Option<Substitutes> {
+    let missing: Vec<_> = buffer
+        .layout_runs()
+        .flat_map(|run| {
+            run.glyphs.iter().filter(|glyph| glyph.glyph_id == 0).map(
+                move |glyph| {
+                    (
+                        run.line_i,
+                        glyph.start..glyph.end,
+                        Point::new(glyph.x, run.line_top),
+                        glyph.w,
+                    )
+                },
+            )
+        })
+        .collect();
+
+    if missing.is_empty() {
+        return None;
+    }
+
+    let mut substitute = glyphon::Buffer::new(font_system, buffer.metrics());
+
+    substitute.set_size(font_system, f32::INFINITY, key.line_height);
+    substitute.set_text(
+        font_system,
+        &replacement.to_string(),
+        to_attrs(key.font),
+        to_shaping(key.shaping),
+    );
+
+    let (glyph_id, width) = substitute
+        .layout_runs()
+        .flat_map(|run| run.glyphs.iter())
+        .map(|glyph| (glyph.glyph_id, glyph.w))
+        .next()?;
+
+    // A replacement that is missing as well would only hide the boxes
+    if glyph_id == 0 {
+        return None;
+    }
+
+    let mut positions = Vec::with_capacity(missing.len());
+
+    // The boxes are hidden instead of replaced in the content, so its byte
+    // ranges stay the same for spans, selections and carets
+    for (line_i, range, position, advance) in missing {
+        positions
+            .push(Point::new(position.x + (advance - width) / 2.0, position.y));
+
+        let Some(line) = buffer.lines.get_mut(line_i) else {
+            continue;
+        };
+
+        let mut attrs_list = line.attrs_list().clone();
+        let attrs = glyphon::AttrsOwned::new(
+            attrs_list
+                .get_span(range.start)
+                .color(glyphon::Color::rgba(0, 0, 0, 0)),
+        );
+
+        attrs_list.add_span(range, attrs.as_attrs());
+
+        let _ = line.set_attrs_list(attrs_list);
+    }
+
+    buffer.shape_until_scroll(font_system);
+
+    Some(Substitutes {
+        buffer: substitute,
+        positions,
+    })
+}
+
+fn substitute_areas(
+    entry: &Entry,
+    left: f32,
+    top: f32,
+    scale: f32,
+    bounds: glyphon::TextBounds,
+    default_color: glyphon::Color,
+) -> impl Iterator<Item = glyphon::TextArea<'_>> {
+    entry.substitutes.iter().flat_map(move |substitutes| {
+        substitutes
+            .positions
+            .iter()
+            .map(move |position| glyphon::TextArea {
+                buffer: &substitutes.buffer,
+                left: left + position.x * scale,
+                top: top + position.y * scale,
+                scale,
+                bounds,
+                default_color,
+            })
+    })
+}
+
+fn justify(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+) {
+    for line in &mut buffer.lines {
+        let _ = line.set_align(Some(glyphon::cosmic_text::Align::Justified));
+    }
+
+    buffer.shape_until_scroll(font_system);
+}
+
+fn fill(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    gradient: Gradient,
+) {
+    let Gradient::Linear(linear) = gradient;
+
+    let line_height = buffer.metrics().line_height;
+    let bounds = Rectangle::with_size(measure(buffer));
+    let (start, end) = linear.angle.to_distance(&bounds);
+    let direction = end - start;
+    let length = direction.x * direction.x + direction.y * direction.y;
+
+    let glyphs: Vec<_> = buffer
+        .layout_runs()
+        .enumerate()
+        .flat_map(|(i, run)| {
+            let center_y = (i as f32 + 0.5) * line_height;
+
+            run.glyphs.iter().map(move |glyph| {
+                let center = Point::new(glyph.x + glyph.w / 2.0, center_y);
+                let offset = center - start;
+
+                let t = if length > 0.0 {
+                    (offset.x * direction.x + offset.y * direction.y) / length
+                } else {
+                    0.0
+                };
+
+                (run.line_i, glyph.start..glyph.end, sample(&linear, t))
+            })
+        })
+        .collect();
+
+    for (line_i, range, color) in glyphs {
+        let Some(line) = buffer.lines.get_mut(line_i) else {
+            continue;
+        };
+
+        let mut attrs_list = line.attrs_list().clone();
+        let attrs = glyphon::AttrsOwned::new(
+            attrs_list.get_span(range.start).color(to_color(color)),
+        );
+
+        attrs_list.add_span(range, attrs.as_attrs());
+
+        let _ = line.set_attrs_list(attrs_list);
+    }
+
+    buffer.shape_until_scroll(font_system);
+}
+
+fn is_colored(buffer: &glyphon::Buffer) -> bool {
+    buffer
+        .layout_runs()
+        .any(|run| run.glyphs.iter().any(|glyph| glyph.color_opt.is_some()))
+}
+
+fn repaint(
+    font_system: &mut glyphon::FontSystem,
+    buffer: &glyphon::Buffer,
+    paint: Paint,
+) -> glyphon::Buffer {
+    let (width, height) = buffer.size();
+
+    let mut painted = glyphon::Buffer::new(font_system, buffer.metrics());
+
+    painted.set_wrap(font_system, buffer.wrap());
+    painted.set_size(font_system, width, height);
+
+    // Lines without colored glyphs keep their layout
+    painted.lines = buffer.lines.clone();
+
+    let glyphs: Vec<_> = buffer
+        .layout_runs()
+        .flat_map(|run| {
+            run.glyphs.iter().filter_map(move |glyph| {
+                let color = glyph.color_opt?;
+                let alpha = f32::from(color.a()) / 255.0;
+
+                let color = match paint {
+                    Paint::Tint(tint) => to_color(Color {
+                        a: tint.a * alpha,
+                        ..tint
+                    }),
+                    Paint::Opacity(opacity) => glyphon::Color::rgba(
+                        color.r(),
+                        color.g(),
+                        color.b(),
+                        (f32::from(color.a()) * opacity).round() as u8,
+                    ),
+                };
+
+                Some((run.line_i, glyph.start..glyph.end, color))
+            })
+        })
+        .collect();
+
+    for (line_i, range, color) in glyphs {
+        let Some(line) = painted.lines.get_mut(line_i) else {
+            continue;
+        };
+
+        let mut attrs_list = line.attrs_list().clone();
+        let attrs = glyphon::AttrsOwned::new(
+            attrs_list.get_span(range.start).color(color),
+        );
+
+        attrs_list.add_span(range, attrs.as_attrs());
+
+        let _ = line.set_attrs_list(attrs_list);
+    }
+
+    painted.shape_until_scroll(font_system);
+
+    painted
+}
+
+fn sample(gradient: &gradient::Linear, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let mut stops = gradient.stops.iter().flatten();
+
+    let Some(first) = stops.next() else {
+        return Color::TRANSPARENT;
+    };
+
+    if t <= first.offset {
+        return first.color;
+    }
+
+    let mut previous = first;
+
+    for stop in stops {
+        if t <= stop.offset {
+            let range = stop.offset - previous.offset;
+            let amount = if range > 0.0 {
+                (t - previous.offset) / range
+            } else {
+                1.0
+            };
+
+            let lerp = |a: f32, b: f32| a + (b - a) * amount;
+
+            return Color::from_rgba(
+                lerp(previous.color.r, stop.color.r),
+                lerp(previous.color.g, stop.color.g),
+                lerp(previous.color.b, stop.color.b),
+                lerp(previous.color.a, stop.color.a),
+            );
+        }
+
+        previous = stop;
+    }
+
+    previous.color
+}
+
+fn ellipsize(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+) {
+    // Lay out the contents in a single line to find where they overflow
+    buffer.set_size(font_system, f32::INFINITY, key.line_height);
+    set_contents(buffer, font_system, key);
+
+    // Only the first line is visible, so any other line does not fit
+    let fits =
+        buffer.lines.len() <= 1 && measure(buffer).width <= key.bounds.width;
+
+    if !fits {
+        truncate(buffer, font_system, key, 0);
+    }
+}
+
+fn limit_lines(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+    max_lines: usize,
+) -> bool {
+    if buffer.layout_runs().count() <= max_lines {
+        return false;
+    }
+
+    if let Some(last_line) = max_lines.checked_sub(1) {
+        truncate(buffer, font_system, key, last_line);
+    } else {
+        set_contents(
+            buffer,
+            font_system,
+            Key {
+                content: "",
+                spans: &[],
+                ..key
+            },
+        );
+    }
+
+    if key.justify {
+        justify(buffer, font_system);
+    }
+
+    true
+}
+
+fn truncate(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+    last_line: usize,
+) {
+    const ELLIPSIS: &str = "\u{2026}";
+
+    // A fading line uses the whole width instead of ending with an ellipsis
+    let suffix = if key.fade.is_some() { "" } else { ELLIPSIS };
+
+    let mut ellipsis = glyphon::Buffer::new(font_system, buffer.metrics());
+
+    ellipsis.set_size(font_system, f32::INFINITY, key.line_height);
+    ellipsis.set_text(
+        font_system,
+        suffix,
+        to_attrs(key.font),
+        to_shaping(key.shaping),
+    );
+
+    let available = key.bounds.width - measure(&ellipsis).width;
+
+    let end = buffer
+        .layout_runs()
+        .nth(last_line)
+        .map(|run| {
+            let line_start: usize = key
+                .content
+                .split_inclusive('\n')
+                .take(run.line_i)
+                .map(str::len)
+                .sum();
+
+            let run_start = run
+                .glyphs
+                .iter()
+                .map(|glyph| glyph.start)
+                .min()
+                .unwrap_or(0);
+
+            let run_end = run
+                .glyphs
+                .iter()
+                .take_while(|glyph| glyph.x + glyph.w <= available)
+                .map(|glyph| glyph.end)
+                .max()
+                .unwrap_or(run_start);
+
+            line_start + run_end
+        })
+        .unwrap_or(0);
+
+    let content = format!("{}{suffix}", key.content[..end].trim_end());
+
+    set_contents(
+        buffer,
+        font_system,
+        Key {
+            content: &content,
+            ..key
+        },
+    );
+}
+
+fn fade(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    width: f32,
+    color: Color,
+) {
+    let Some(run) = buffer.layout_runs().last() else {
+        return;
+    };
+
+    let line_i = run.line_i;
+    let start = run.line_w - width;
+
+    let glyphs: Vec<_> = run
+        .glyphs
+        .iter()
+        .filter(|glyph| glyph.x + glyph.w > start)
+        .map(|glyph| {
+            let center = glyph.x + glyph.w / 2.0;
+            let alpha = if width > 0.0 {
+                ((run.line_w - center) / width).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+
+            (glyph.start..glyph.end, alpha)
+        })
+        .collect();
+
+    let Some(line) = buffer.lines.get_mut(line_i) else {
+        return;
+    };
+
+    let mut attrs_list = line.attrs_list().clone();
+
+    for (range, alpha) in glyphs {
+        let span = attrs_list.get_span(range.start);
+        let glyph_color = span.color_opt.unwrap_or(to_color(color));
+
+        let attrs = glyphon::AttrsOwned::new(span.color(glyphon::Color::rgba(
+            glyph_color.r(),
+            glyph_color.g(),
+            glyph_color.b(),
+            (f32::from(glyph_color.a()) * alpha).round() as u8,
+        )));
+
+        attrs_list.add_span(range, attrs.as_attrs());
+    }
+
+    let _ = line.set_attrs_list(attrs_list);
+
+    buffer.shape_until_scroll(font_system);
+}
 
-        (hash, self.entries.get_mut(&hash).unwrap())
-    }
+fn set_rich_text(
+    buffer: &mut glyphon::Buffer,
+    font_system: &mut glyphon::FontSystem,
+    key: Key<'_>,
+) {
+    let attrs = to_attrs(key.font);
 
-    fn trim(&mut self, purpose: Purpose) {
-        self.entries.retain(|key, _| {
-            self.recently_measured.contains(key)
-                || self.recently_drawn.contains(key)
-        });
-        self.aliases.retain(|_, value| {
-            self.recently_measured.contains(value)
-                || self.recently_drawn.contains(value)
-        });
+    buffer.lines.clear();
 
-        match purpose {
-            Purpose::Measuring => {
-                self.recently_measured.clear();
+    // Split lines the same way `glyphon::Buffer::set_text` does, while
+    // keeping track of their offsets to map the spans into each line.
+    let mut offset = 0;
+
+    for line in key.content.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+
+        let mut attrs_list = glyphon::AttrsList::new(attrs);
+
+        for span in key.spans {
+            let start = span.range.start.max(offset);
+            let end = span.range.end.min(offset + text.len());
+
+            if start >= end {
+                continue;
             }
-            Purpose::Drawing => {
-                self.recently_drawn.clear();
+
+            let mut span_attrs = span.font.map(to_attrs).unwrap_or(attrs);
+
+            if let Some(color) = span.color {
+                span_attrs = span_attrs.color(to_color(color));
             }
+
+            attrs_list.add_span(start - offset..end - offset, span_attrs);
         }
+
+        buffer.lines.push(glyphon::BufferLine::new(
+            text,
+            attrs_list,
+            to_shaping(key.shaping),
+        ));
+
+        offset += line.len();
+    }
+
+    if buffer.lines.is_empty() {
+        buffer.lines.push(glyphon::BufferLine::new(
+            "",
+            glyphon::AttrsList::new(attrs),
+            to_shaping(key.shaping),
+        ));
     }
+
+    buffer.shape_until_scroll(font_system);
 }
 
 #[derive(Debug, Clone, Copy)]
 struct Key<'a> {
     content: &'a str,
+    content_id: Option<u64>,
+    spans: &'a [primitive::Span],
     size: f32,
     line_height: f32,
     font: Font,
     bounds: Size,
     shaping: Shaping,
+    ellipsis: bool,
+    justify: bool,
+    max_lines: Option<usize>,
+    fade: Option<(f32, Color)>,
+    wrap: primitive::Wrap,
+    gradient: Option<Gradient>,
+    language: Option<&'static str>,
+    tab_width: Option<usize>,
+    paragraph_spacing: f32,
 }
 
 impl Key<'_> {
     fn hash<H: Hasher>(self, mut hasher: H) -> KeyHash {
-        self.content.hash(&mut hasher);
+        // Long contents are slow to hash, so a caller may identify them
+        match self.content_id {
+            Some(id) => id.hash(&mut hasher),
+            None => self.content.hash(&mut hasher),
+        }
+
+        for span in self.spans {
+            span.range.hash(&mut hasher);
+            span.font.hash(&mut hasher);
+            span.color
+                .map(|color| color.into_linear().map(f32::to_bits))
+                .hash(&mut hasher);
+        }
+
         self.size.to_bits().hash(&mut hasher);
         self.line_height.to_bits().hash(&mut hasher);
         self.font.hash(&mut hasher);
         self.bounds.width.to_bits().hash(&mut hasher);
         self.bounds.height.to_bits().hash(&mut hasher);
         self.shaping.hash(&mut hasher);
+        self.ellipsis.hash(&mut hasher);
+        self.justify.hash(&mut hasher);
+        self.max_lines.hash(&mut hasher);
+        self.fade
+            .map(|(width, color)| {
+                (width.to_bits(), color.into_linear().map(f32::to_bits))
+            })
+            .hash(&mut hasher);
+        self.wrap.hash(&mut hasher);
+        self.language.hash(&mut hasher);
+        self.tab_width.hash(&mut hasher);
+        self.paragraph_spacing.to_bits().hash(&mut hasher);
+
+        if let Some(Gradient::Linear(linear)) = self.gradient {
+            linear.angle.0.to_bits().hash(&mut hasher);
+
+            for stop in linear.stops.iter().flatten() {
+                stop.offset.to_bits().hash(&mut hasher);
+                stop.color.into_linear().map(f32::to_bits).hash(&mut hasher);
+            }
+        }
 
         hasher.finish()
     }
 }
 
+impl<'a> From<&Text<'a>> for Key<'a> {
+    fn from(text: &Text<'a>) -> Self {
+        Key {
+            content: text.content,
+            content_id: text.content_id,
+            spans: text.spans,
+            size: text.size,
+            line_height: f32::from(
+                text.line_height.to_absolute(Pixels(text.size)),
+            ),
+            font: text.font,
+            bounds: text.bounds.size(),
+            shaping: text.shaping,
+            ellipsis: text.ellipsis,
+            justify: text.horizontal_alignment
+                == alignment::Horizontal::Justify,
+            max_lines: text.max_lines,
+            fade: match text.clamp {
+                primitive::LineClamp::Fade { width }
+                    if text.max_lines.is_some() =>
+                {
+                    Some((width, text.color))
+                }
+                _ => None,
+            },
+            wrap: text.wrap,
+            gradient: text.gradient,
+            language: text.language,
+            tab_width: text.tab_width,
+            paragraph_spacing: text.paragraph_spacing,
+        }
+    }
+}
+
+impl<'a> From<&MeasureRequest<'a>> for Key<'a> {
+    fn from(request: &MeasureRequest<'a>) -> Self {
+        Key {
+            content: request.content,
+            content_id: None,
+            spans: &[],
+            size: request.size,
+            line_height: f32::from(
+                request.line_height.to_absolute(Pixels(request.size)),
+            ),
+            font: request.font,
+            bounds: request.bounds,
+            shaping: request.shaping,
+            ellipsis: false,
+            justify: false,
+            max_lines: None,
+            fade: None,
+            wrap: primitive::Wrap::Word,
+            gradient: None,
+            language: None,
+            tab_width: None,
+            paragraph_spacing: 0.0,
+        }
+    }
+}
+
 type KeyHash = u64;
+
+/// A handle to a text buffer pinned in the cache of a [`Backend`].
+///
+/// A pinned buffer is never trimmed nor evicted until it is unpinned. When the
+/// cache is cleared or its fonts are removed, it is shaped again with the
+/// fonts that are left, so the [`Handle`] stays valid.
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle(KeyHash);
+
+/// A way to build the hashers that key the text cache of a [`Backend`].
+///
+/// The hashers of the text cache only need to be good enough to tell text
+/// apart, and a deterministic one makes the cache behave the same way on
+/// every run.
+///
+/// [`Backend`]: crate::Backend
+#[derive(Clone, Copy)]
+pub struct CacheHasher(fn() -> Box<dyn Hasher>);
+
+impl CacheHasher {
+    /// Creates a [`CacheHasher`] from a function that builds a new hasher.
+    pub fn new(build: fn() -> Box<dyn Hasher>) -> Self {
+        Self(build)
+    }
+
+    /// Creates a [`CacheHasher`] that builds default hashers of type `H`.
+    pub fn of<H: Hasher + Default + 'static>() -> Self {
+        Self(|| -> Box<dyn Hasher> { Box::<H>::default() })
+    }
+}
+
+impl fmt::Debug for CacheHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CacheHasher").finish()
+    }
+}
+
+impl PartialEq for CacheHasher {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 as usize == other.0 as usize
+    }
+}
+
+/// Some plain text to be measured in a batch by a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeasureRequest<'a> {
+    /// The content of the text.
+    pub content: &'a str,
+
+    /// The size of the text in logical pixels.
+    pub size: f32,
+
+    /// The line height of the text.
+    pub line_height: LineHeight,
+
+    /// The font of the text.
+    pub font: Font,
+
+    /// The maximum bounds of the text.
+    pub bounds: Size,
+
+    /// The shaping strategy of the text.
+    pub shaping: Shaping,
+}
+
+/// A handle to the faces of a font loaded by a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontHandle {
+    faces: Vec<glyphon::fontdb::ID>,
+    families: Vec<String>,
+}
+
+impl FontHandle {
+    /// Returns the family names of the loaded font, as found in its file.
+    ///
+    /// Any of these names can be used to reference the font with
+    /// [`Family::Name`].
+    ///
+    /// [`Family::Name`]: crate::core::font::Family::Name
+    pub fn families(&self) -> &[String] {
+        &self.families
+    }
+}
+
+/// The outcome of preparing the text of a frame in a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Preparation {
+    /// All of the text was prepared successfully.
+    ///
+    /// The glyph atlas may have grown to fit it, which happens transparently.
+    #[default]
+    Prepared,
+
+    /// The glyph atlas was full and could not grow any further.
+    ///
+    /// Some of the text may be drawn with missing glyphs.
+    AtlasExhausted,
+}
+
+/// The complexity of some text laid out by a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Complexity {
+    /// The amount of glyphs of the text.
+    pub glyphs: usize,
+
+    /// The amount of visual lines of the text.
+    pub lines: usize,
+}
+
+/// The overflow of some text measured with a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overflow {
+    /// The minimum bounds that can fit every line of the text.
+    pub bounds: Size,
+
+    /// Whether any line of the text does not fit its bounds.
+    pub overflows: bool,
+
+    /// The amount of lines that do not fit the bounds of the text.
+    ///
+    /// A line that is only partially visible counts as clipped, like every
+    /// line of bounds shorter than a single line.
+    pub clipped_lines: usize,
+}
+
+/// The details of a text atlas of a [`Backend`] that was full and could not
+/// grow any further.
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhaustion {
+    /// The amount of text sections that were being prepared.
+    pub sections: usize,
+
+    /// The amount of times the text atlas has been full so far, including
+    /// this one.
+    pub overflows: u64,
+}
+
+/// The metrics of some text measured with a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    /// The minimum bounds that can fit the text.
+    pub bounds: Size,
+
+    /// The distance from the baseline to the top of the primary font.
+    pub ascent: f32,
+
+    /// The distance from the baseline to the bottom of the primary font.
+    pub descent: f32,
+
+    /// The recommended gap between lines of the primary font.
+    pub line_gap: f32,
+
+    /// The distance from the top of the text to its first baseline.
+    pub baseline: f32,
+}
+
+/// The detailed result of hit testing some text with a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitDetails {
+    /// The character offset that was hit, relative to the start of its line.
+    pub index: usize,
+
+    /// The index of the visual line that was hit.
+    ///
+    /// Wrapped lines count as multiple visual lines.
+    pub line: usize,
+
+    /// The horizontal offset of the character that was hit, relative to the
+    /// start of its visual line.
+    pub x: f32,
+}
+
+/// Statistics about the text cache of a [`Backend`].
+///
+/// [`Backend`]: crate::Backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// The amount of text buffers currently cached.
+    pub entries: usize,
+
+    /// The amount of aliases pointing to cached buffers with different bounds.
+    pub aliases: usize,
+
+    /// The estimated amount of memory used by the cached buffers, in bytes.
+    pub memory: usize,
+
+    /// The lookups performed while measuring text.
+    pub measuring: CacheUsage,
+
+    /// The lookups performed while drawing text.
+    pub drawing: CacheUsage,
+}
+
+/// The hits and misses of a certain kind of text cache lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheUsage {
+    /// The amount of lookups that reused a cached buffer.
+    pub hits: u64,
+
+    /// The amount of lookups that needed to shape a new buffer.
+    pub misses: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::core::Radians;
+
+    fn request(content: &str) -> MeasureRequest<'_> {
+        MeasureRequest {
+            content,
+            size: 16.0,
+            line_height: LineHeight::default(),
+            font: Font::default(),
+            bounds: Size::INFINITY,
+            shaping: Shaping::Basic,
+        }
+    }
+
+    fn hash(hasher: &HashBuilder, content: &str) -> KeyHash {
+        Key::from(&request(content)).hash(hasher.build_hasher())
+    }
+
+    fn icons() -> (glyphon::FontSystem, Font) {
+        let mut db = glyphon::fontdb::Database::new();
+        db.load_font_data(include_bytes!("../fonts/Iced-Icons.ttf").to_vec());
+
+        (
+            glyphon::FontSystem::new_with_locale_and_db("en-US".into(), db),
+            Font::with_name("Iced-Icons"),
+        )
+    }
+
+    #[test]
+    fn seeded_hashes_are_deterministic() {
+        let a = HashBuilder::new(Some(42));
+        let b = HashBuilder::new(Some(42));
+
+        assert_eq!(hash(&a, "Hello, world!"), hash(&b, "Hello, world!"));
+        assert_ne!(hash(&a, "Hello, world!"), hash(&a, "Hello, world?"));
+        assert_ne!(
+            hash(&a, "Hello, world!"),
+            hash(&HashBuilder::new(Some(43)), "Hello, world!")
+        );
+    }
+
+    #[test]
+    fn custom_hashes_are_deterministic() {
+        let hasher = CacheHasher::of::<hash_map::DefaultHasher>();
+
+        let a = HashBuilder::Custom(hasher);
+        let b = HashBuilder::Custom(hasher);
+
+        assert_eq!(hash(&a, "Hello, world!"), hash(&b, "Hello, world!"));
+        assert_ne!(hash(&a, "Hello, world!"), hash(&a, "Hello, world?"));
+    }
+
+    #[test]
+    fn missing_glyphs_keep_their_content() {
+        let (mut font_system, font) = icons();
+
+        let content = "\u{e800}é漢\u{f00c}\n\u{e800}";
+        let key = Key {
+            max_lines: Some(1),
+            ..Key::from(&MeasureRequest {
+                font,
+                ..request(content)
+            })
+        };
+
+        let mut buffer = shape(&mut font_system, key);
+        let substitutes =
+            substitute(&mut buffer, &mut font_system, key, '\u{e800}')
+                .expect("Substitute missing glyphs");
+
+        // The truncated line ends with an ellipsis, which is missing too
+        assert!(buffer.lines[0].text().starts_with("\u{e800}é漢\u{f00c}"));
+
+        let missing = buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs.iter())
+            .inspect(|glyph| {
+                let is_hidden =
+                    glyph.color_opt.map_or(false, |color| color.a() == 0);
+
+                assert_eq!(glyph.glyph_id == 0, is_hidden);
+            })
+            .filter(|glyph| glyph.glyph_id == 0)
+            .count();
+
+        assert_eq!(substitutes.positions.len(), missing);
+        assert!(missing >= 2);
+    }
+
+    #[test]
+    fn missing_replacements_are_not_substituted() {
+        let (mut font_system, font) = icons();
+
+        let content = "\u{e800}é";
+        let key = Key::from(&MeasureRequest {
+            font,
+            ..request(content)
+        });
+
+        let mut buffer = shape(&mut font_system, key);
+
+        assert!(substitute(&mut buffer, &mut font_system, key, '漢').is_none());
+    }
+
+    #[test]
+    fn hits_follow_the_direction_of_glyphs() {
+        let (mut font_system, font) = icons();
+
+        let content = "abc אבג";
+        let buffer = shape(
+            &mut font_system,
+            Key::from(&MeasureRequest {
+                font,
+                shaping: Shaping::Advanced,
+                ..request(content)
+            }),
+        );
+
+        let y = buffer.metrics().line_height / 2.0;
+
+        let glyphs: Vec<_> = buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs.iter())
+            .filter(|glyph| glyph.w > 0.0)
+            .map(|glyph| {
+                (
+                    glyph.x,
+                    glyph.w,
+                    glyph.start,
+                    glyph.end,
+                    glyph.level.is_rtl(),
+                )
+            })
+            .collect();
+
+        assert!(glyphs.iter().any(|(.., is_rtl)| *is_rtl));
+        assert!(glyphs.iter().any(|(.., is_rtl)| !*is_rtl));
+
+        // The logical start of right-to-left glyphs is on their right half
+        for (x, width, start, end, is_rtl) in glyphs {
+            let left = hit(
+                &buffer,
+                &Paragraphs::default(),
+                Point::new(x + width * 0.25, y),
+                true,
+            )
+            .expect("Hit left half");
+            let right = hit(
+                &buffer,
+                &Paragraphs::default(),
+                Point::new(x + width * 0.75, y),
+                true,
+            )
+            .expect("Hit right half");
+
+            let (left_index, right_index) =
+                if is_rtl { (end, start) } else { (start, end) };
+
+            assert_eq!(left.index, left_index);
+            assert_eq!(right.index, right_index);
+        }
+    }
+
+    #[test]
+    fn graphemes_are_never_split() {
+        // A combining accent and a family of emoji joined together
+        let content = "ae\u{301}\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}b";
+
+        assert_eq!(grapheme_start(content, 2), 1);
+        assert_eq!(grapheme_start(content, 10), 4);
+        assert_eq!(grapheme_start(content, 22), 22);
+        assert_eq!(grapheme_start(content, content.len()), content.len());
+
+        assert_eq!(next_grapheme(content, 0), 1);
+        assert_eq!(next_grapheme(content, 2), 4);
+        assert_eq!(next_grapheme(content, 4), 22);
+        assert_eq!(next_grapheme(content, 22), content.len());
+        assert_eq!(next_grapheme(content, content.len()), content.len());
+
+        assert_eq!(previous_grapheme(content, content.len()), 22);
+        assert_eq!(previous_grapheme(content, 22), 4);
+        assert_eq!(previous_grapheme(content, 10), 4);
+        assert_eq!(previous_grapheme(content, 4), 1);
+        assert_eq!(previous_grapheme(content, 0), 0);
+    }
+
+    #[test]
+    fn invalid_metrics_are_sanitized() {
+        let key = sanitize(Key {
+            size: f32::NAN,
+            line_height: -1.0,
+            bounds: Size::new(f32::NAN, -10.0),
+            ..Key::from(&request("Hello"))
+        });
+
+        assert_eq!(key.size, 16.0);
+        assert_eq!(key.line_height, 16.0 * 1.3);
+        assert_eq!(key.bounds, Size::new(f32::INFINITY, 0.0));
+
+        // Any bounds too large to lay out are unbounded, so they share the
+        // same entry
+        let hasher = HashBuilder::new(Some(42));
+
+        let unbounded = Key::from(&request("Hello"));
+        let huge = Key {
+            bounds: Size::new(f32::MAX, f32::MAX),
+            ..unbounded
+        };
+
+        assert_eq!(
+            sanitize(unbounded).hash(hasher.build_hasher()),
+            sanitize(huge).hash(hasher.build_hasher())
+        );
+    }
+
+    #[test]
+    fn gradients_are_sampled_between_stops() {
+        let linear = gradient::Linear::new(Radians(0.0))
+            .add_stop(0.25, Color::BLACK)
+            .add_stop(0.75, Color::WHITE);
+
+        assert_eq!(sample(&linear, 0.0), Color::BLACK);
+        assert_eq!(sample(&linear, 0.5), Color::from_rgb(0.5, 0.5, 0.5));
+        assert_eq!(sample(&linear, 1.0), Color::WHITE);
+
+        assert_eq!(
+            sample(&gradient::Linear::new(Radians(0.0)), 0.5),
+            Color::TRANSPARENT
+        );
+    }
+
+    #[test]
+    fn colors_are_rounded() {
+        let color = to_color(Color::from_rgba(1.0, 0.0, 1.0, 0.5));
+
+        assert_eq!(
+            (color.r(), color.g(), color.b(), color.a()),
+            (255, 0, 255, 128)
+        );
+
+        assert_eq!(to_color(Color::from_rgba(0.0, 0.0, 0.0, 0.999)).a(), 255);
+        assert_eq!(to_color(Color::TRANSPARENT).a(), 0);
+    }
+
+    #[test]
+    fn carets_after_a_trailing_line_break_start_a_new_line() {
+        let (mut font_system, font) = icons();
+
+        let content = "ab\n";
+        let buffer = shape(
+            &mut font_system,
+            Key::from(&MeasureRequest {
+                font,
+                ..request(content)
+            }),
+        );
+
+        let line_height = buffer.metrics().line_height;
+
+        let before = caret(&buffer, &Paragraphs::default(), content, 2)
+            .expect("Caret before break");
+        let end =
+            caret(&buffer, &Paragraphs::default(), content, content.len())
+                .expect("Caret at end");
+
+        assert_eq!(before.y, 0.0);
+        assert!(before.x > 0.0);
+
+        assert_eq!(end.x, 0.0);
+        assert_eq!(end.y, line_height);
+    }
+
+    #[test]
+    fn right_to_left_paragraphs_swap_alignments() {
+        let (mut font_system, font) = icons();
+        let mut cache = Cache::new(None, None, HashBuilder::new(Some(0)), None);
+
+        let bounds =
+            Rectangle::new(Point::new(10.0, 0.0), Size::new(200.0, 50.0));
+
+        // An Arabic paragraph with some Latin text in the middle
+        let text = primitive::Text {
+            content: String::from("مرحبا abc بالعالم"),
+            font,
+            shaping: Shaping::Advanced,
+            bounds,
+            ..primitive::Text::default()
+        };
+
+        let section = Text::from(&text);
+        let (_, entry) = cache.allocate(
+            &mut font_system,
+            Key::from(&section),
+            Purpose::Drawing,
+        );
+
+        assert!(entry.buffer.layout_runs().all(|run| run.rtl));
+
+        let left = |section: &Text<'_>| {
+            align(section, entry, 1.0).x + ink_offset(section, entry)
+        };
+
+        let width = entry.bounds.width;
+
+        assert!(
+            (left(&section) - (bounds.x + bounds.width - width)).abs() < 0.01
+        );
+
+        let section = Text {
+            horizontal_alignment: alignment::Horizontal::Right,
+            ..section
+        };
+
+        assert!((left(&section) - (bounds.x - bounds.width)).abs() < 0.01);
+    }
+
+    #[test]
+    fn languages_are_cached_separately() {
+        let hasher = HashBuilder::new(Some(42));
+
+        // A unified ideograph drawn differently in Chinese and Japanese
+        let chinese = primitive::Text {
+            content: String::from("直"),
+            language: Some("zh"),
+            ..primitive::Text::default()
+        };
+
+        let japanese = primitive::Text {
+            language: Some("ja"),
+            ..chinese.clone()
+        };
+
+        let hash = |text: &primitive::Text| {
+            Key::from(&Text::from(text)).hash(hasher.build_hasher())
+        };
+
+        assert_ne!(hash(&chinese), hash(&japanese));
+    }
+
+    #[test]
+    fn tabs_are_expanded_to_the_next_tab_stop() {
+        let text = primitive::Text {
+            content: String::from("a\tb\n\tc"),
+            tab_width: Some(4),
+            ..primitive::Text::default()
+        };
+
+        let text = Text::from(&text);
+        let tabs = Tabs::new(Key::from(&text)).unwrap();
+
+        assert_eq!(tabs.content, "a   b\n    c");
+
+        // Offsets after a tab move past all of its spaces
+        assert_eq!(tabs.expand(1), 1);
+        assert_eq!(tabs.expand(2), 4);
+        assert_eq!(tabs.expand(4), 6);
+        assert_eq!(tabs.expand(5), 10);
+
+        // Offsets inside of a tab snap to its closest edge
+        assert_eq!(tabs.collapse(2), 1);
+        assert_eq!(tabs.collapse(3), 2);
+        assert_eq!(tabs.collapse(4), 2);
+        assert_eq!(tabs.collapse(10), 5);
+
+        let hasher = HashBuilder::new(Some(42));
+        let hash = |key: Key<'_>| key.hash(hasher.build_hasher());
+
+        assert_ne!(
+            hash(Key::from(&text)),
+            hash(Key {
+                tab_width: Some(8),
+                ..Key::from(&text)
+            })
+        );
+    }
+
+    #[test]
+    fn paragraphs_are_spaced_apart() {
+        let (mut font_system, font) = icons();
+
+        let content = "a\nb\n\nc";
+        let buffer = shape(
+            &mut font_system,
+            Key::from(&MeasureRequest {
+                font,
+                ..request(content)
+            }),
+        );
+
+        let line_height = buffer.metrics().line_height;
+        let paragraphs = Paragraphs::new(&buffer, 10.0);
+
+        // Single line breaks stay in the same paragraph
+        assert_eq!(paragraphs.offset(1), 0.0);
+        assert_eq!(paragraphs.offset(2), 0.0);
+        assert_eq!(paragraphs.offset(3), 10.0);
+
+        assert_eq!(
+            paragraphs.measure(&buffer).height,
+            4.0 * line_height + 10.0
+        );
+
+        let caret = caret(&buffer, &paragraphs, content, content.len())
+            .expect("Caret at end");
+
+        assert_eq!(caret.y, 3.0 * line_height + 10.0);
+
+        // The gap before a paragraph is not on any of its lines
+        let gap = Point::new(0.0, 3.0 * line_height + 8.0);
+
+        assert!(hit(&buffer, &paragraphs, gap, false).is_none());
+        assert_eq!(
+            hit(&buffer, &paragraphs, gap, true).map(|details| details.line),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn colored_emoji_are_painted_as_colored_glyphs() {
+        let (mut font_system, font) = icons();
+        let mut cache = Cache::new(None, None, HashBuilder::new(Some(0)), None);
+
+        // No color font is bundled, so the emoji gets its color from a span
+        let text = primitive::Text {
+            content: String::from("Party 🎉"),
+            spans: vec![primitive::Span {
+                color: Some(Color::from_rgb(1.0, 0.5, 0.0)),
+                ..primitive::Span::new(6..10)
+            }],
+            font,
+            shaping: Shaping::Advanced,
+            ..primitive::Text::default()
+        };
+
+        let section = Text::from(&text);
+        let (hash, entry) = cache.allocate(
+            &mut font_system,
+            Key::from(&section),
+            Purpose::Drawing,
+        );
+
+        assert!(entry.colored);
+
+        let emoji: Vec<_> = entry
+            .buffer
+            .layout_runs()
+            .flat_map(|run| run.glyphs.iter())
+            .filter(|glyph| glyph.color_opt.is_some())
+            .map(|glyph| glyph.start..glyph.end)
+            .collect();
+
+        assert_eq!(emoji, vec![6..10]);
+
+        // Fading the text keeps the color of the emoji in its own variant
+        cache.paint(&mut font_system, hash, Paint::Opacity(0.5));
+
+        let entry = cache.get(&hash).expect("Get cached buffer");
+
+        assert_eq!(entry.variants.len(), 1);
+    }
+}
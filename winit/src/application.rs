@@ -888,11 +888,10 @@ pub fn run_command<A, C, E>(
             command::Action::LoadFont { bytes, tagger } => {
                 use crate::core::text::Renderer;
 
-                // TODO: Error handling (?)
-                renderer.load_font(bytes);
+                let result = renderer.load_font(bytes);
 
                 proxy
-                    .send_event(tagger(Ok(())))
+                    .send_event(tagger(result))
                     .expect("Send message to event loop");
             }
         }